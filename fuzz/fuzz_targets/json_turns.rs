@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use silviator::models::Input;
+
+// Raw protocol lines, one turn per line, like the server sends them
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let turns: Vec<Input> = text
+        .lines()
+        .map_while(|line| serde_json::from_str(line).ok())
+        .collect();
+
+    silviator_fuzz::play(turns);
+});
@@ -0,0 +1,101 @@
+#![no_main]
+
+use arbitrary::{Result, Unstructured};
+use libfuzzer_sys::fuzz_target;
+use serde_json::Value;
+use silviator_fuzz::{play, GAMES};
+
+fn number(u: &mut Unstructured) -> Result<Value> {
+    Ok(match u.int_in_range(0..=3)? {
+        0 => Value::from(u.int_in_range(-2..=2)?),
+        1 => Value::from(u.arbitrary::<i32>()?),
+        2 => Value::from(u.arbitrary::<f32>()?),
+        _ => Value::from(u.int_in_range(0..=1000)?),
+    })
+}
+
+/// Changes one field of a planet or expedition somewhere in the game
+fn mutate(u: &mut Unstructured, turns: &mut [Value]) -> Result<()> {
+    let names: Vec<_> = turns[0]["planets"]
+        .as_array()
+        .map(|x| x.iter().map(|p| p["name"].clone()).collect())
+        .unwrap_or_default();
+
+    let turn = u.choose_index(turns.len())?;
+    let list = if u.arbitrary()? {
+        "planets"
+    } else {
+        "expeditions"
+    };
+    let Some(items) = turns[turn][list].as_array_mut() else {
+        return Ok(());
+    };
+    if items.is_empty() {
+        return Ok(());
+    }
+    let i = u.choose_index(items.len())?;
+
+    if u.ratio(1, 10)? {
+        items.remove(i);
+        return Ok(());
+    }
+
+    let item = &mut items[i];
+    let fields = [
+        "ship_count",
+        "x",
+        "y",
+        "owner",
+        "turns_remaining",
+        "id",
+        "name",
+        "origin",
+        "destination",
+    ];
+    let field = *u.choose(&fields)?;
+    if item.get(field).is_none() {
+        return Ok(());
+    }
+
+    item[field] = match field {
+        "name" | "origin" | "destination" => {
+            if names.is_empty() || u.ratio(1, 4)? {
+                Value::from(u.arbitrary::<String>()?)
+            } else {
+                u.choose(&names)?.clone()
+            }
+        }
+        "owner" if u.ratio(1, 4)? => Value::Null,
+        _ => number(u)?,
+    };
+    Ok(())
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+    let Ok(game) = u.choose(&GAMES) else {
+        return;
+    };
+
+    let mut turns: Vec<Value> = game
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    // Short games keep the runs fast, most invariants break early anyway
+    let Ok(len) = u.int_in_range(1..=turns.len().min(40)) else {
+        return;
+    };
+    turns.truncate(len);
+
+    while let Ok(true) = u.arbitrary() {
+        if mutate(&mut u, &mut turns).is_err() {
+            break;
+        }
+    }
+
+    // Mutations may break the protocol itself, those turns are cut off like a closed stdin
+    let turns = turns
+        .into_iter()
+        .map_while(|turn| serde_json::from_value(turn).ok());
+    play(turns);
+});
@@ -0,0 +1,54 @@
+use std::{collections::HashMap, sync::atomic::AtomicBool, time::Instant};
+
+use silviator::{models::*, turn};
+
+pub const GAMES: [&str; 3] = [
+    include_str!("../../tests/success_hex.txt"),
+    include_str!("../../tests/success_spiral.txt"),
+    include_str!("../../tests/fail_hungergames.txt"),
+];
+
+/// Plays the turns like the bot does, panics when a planned move breaks the rules
+pub fn play(turns: impl IntoIterator<Item = Input>) {
+    let mut turns = turns.into_iter();
+    let Some(first) = turns.next() else {
+        return;
+    };
+
+    let mut state = State::new(first);
+    for turn_count in 0.. {
+        turn(
+            &mut state,
+            Instant::now(),
+            turn_count,
+            &AtomicBool::new(false),
+        );
+        check_moves(&state, state.pending_moves());
+        state.take_moves();
+
+        match turns.next() {
+            Some(input) => state.turn(input),
+            None => break,
+        }
+    }
+}
+
+/// Checks the moves as the planner made them, before `take_moves` drops or clamps any
+fn check_moves(state: &State, moves: &[(usize, usize, i32)]) {
+    let mut sent: HashMap<usize, i32> = HashMap::new();
+
+    for &(source, target, ships) in moves {
+        let planet = &state.planets()[source][0];
+        assert_eq!(planet.owner, ME, "move from a planet that is not ours");
+        assert_ne!(source, target, "move to itself");
+        assert!(ships > 0, "move without ships");
+        assert!(target < state.planets().len(), "move to an unknown planet");
+
+        let total = sent.entry(source).or_default();
+        *total += ships;
+        assert!(
+            *total <= planet.ships,
+            "sending more ships than the planet has"
+        );
+    }
+}
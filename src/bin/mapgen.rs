@@ -0,0 +1,32 @@
+use std::{env, error::Error};
+
+use silviator::mapgen::{generate, MapOptions};
+
+const USAGE: &str = "usage: mapgen [--family hex|spiral|scatter] [--players N] [--planets N] \
+                     [--spread D] [--home-ships N] [--neutral-ships N] [--seed N]";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let mut options = MapOptions::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(USAGE);
+        match arg.as_str() {
+            "--family" => options.family = value()?.parse()?,
+            "--players" => options.players = value()?.parse()?,
+            "--planets" => options.planets = value()?.parse()?,
+            "--spread" => options.spread = value()?.parse()?,
+            "--home-ships" => options.home_ships = value()?.parse()?,
+            "--neutral-ships" => options.neutral_ships = value()?.parse()?,
+            "--seed" => options.seed = value()?.parse()?,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    println!("{}", serde_json::to_string(&generate(&options)?)?);
+    Ok(())
+}
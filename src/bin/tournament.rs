@@ -0,0 +1,292 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use silviator::{
+    engine::{Game, Map, Moves, Outcome},
+    mapgen::{generate, Family, MapOptions},
+};
+
+const USAGE: &str = "usage: tournament --bot NAME=COMMAND --bot NAME=COMMAND... [--map FILE]... \
+                     [--generate N] [--ratings FILE] [--rounds N] [--max-turns N]";
+
+/// A bot gets some slack over the server deadline, process startup is not free
+const MOVE_TIMEOUT: Duration = Duration::from_secs(2);
+const K: f64 = 32.;
+
+struct Bot {
+    name: String,
+    command: String,
+}
+
+struct Player {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    lines: Receiver<String>,
+}
+
+impl Player {
+    fn spawn(bot: &Bot) -> Result<Self, Box<dyn Error>> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&bot.command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdout = child.stdout.take().ok_or("No stdout")?;
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            stdin: child.stdin.take(),
+            child,
+            lines,
+        })
+    }
+
+    /// Sends the state and waits for moves, a crashed or slow bot does nothing
+    fn play(&mut self, view: &str) -> Moves {
+        let sent = self.stdin.as_mut().is_some_and(|stdin| {
+            writeln!(stdin, "{}", view)
+                .and_then(|_| stdin.flush())
+                .is_ok()
+        });
+
+        let moves = sent
+            .then(|| self.lines.recv_timeout(MOVE_TIMEOUT).ok())
+            .flatten()
+            .and_then(|line| serde_json::from_str(&line).ok());
+
+        moves.unwrap_or_else(|| {
+            self.stdin = None;
+            Moves { moves: Vec::new() }
+        })
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        self.stdin = None;
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Plays one game, returns the seat of the winner
+fn play(map: &Map, bots: [&Bot; 2], max_turns: usize) -> Result<Option<usize>, Box<dyn Error>> {
+    let mut game = Game::new(map.clone(), max_turns);
+    let mut players = bots
+        .iter()
+        .map(|bot| Player::spawn(bot))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    loop {
+        if let Outcome::Finished(winner) = game.outcome() {
+            return Ok(winner.map(|x| x - 1));
+        }
+
+        let moves: Vec<_> = players
+            .iter_mut()
+            .enumerate()
+            .map(|(i, p)| p.play(&game.view(i + 1)))
+            .collect();
+        for (i, m) in moves.iter().enumerate() {
+            game.dispatch(i + 1, &m.moves);
+        }
+        game.step();
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Rating {
+    elo: f64,
+    games: usize,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            elo: 1500.,
+            games: 0,
+        }
+    }
+}
+
+/// `score` is 1 when `a` won, 0.5 for a draw
+fn update_elo(ratings: &mut BTreeMap<String, Rating>, a: &str, b: &str, score: f64) {
+    let ra = ratings.entry(a.to_string()).or_default().elo;
+    let rb = ratings.entry(b.to_string()).or_default().elo;
+    let expected = 1. / (1. + 10f64.powf((rb - ra) / 400.));
+
+    let delta = K * (score - expected);
+    for (name, delta) in [(a, delta), (b, -delta)] {
+        let rating = ratings.get_mut(name).unwrap();
+        rating.elo += delta;
+        rating.games += 1;
+    }
+}
+
+fn load_map(path: &str) -> Result<Map, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+    let first = content
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .ok_or("Map file is empty")?;
+    Ok(serde_json::from_str(first)?)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+
+    let mut bots = Vec::new();
+    let mut maps = Vec::new();
+    let mut generated = 0;
+    let mut ratings_path = "ratings.json".to_string();
+    let mut rounds = 1;
+    let mut max_turns = 500;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bot" => {
+                let spec = args.next().ok_or(USAGE)?;
+                let (name, command) = spec.split_once('=').ok_or(USAGE)?;
+                bots.push(Bot {
+                    name: name.to_string(),
+                    command: command.to_string(),
+                });
+            }
+            "--map" => maps.push(args.next().ok_or(USAGE)?),
+            "--generate" => generated = args.next().ok_or(USAGE)?.parse()?,
+            "--ratings" => ratings_path = args.next().ok_or(USAGE)?,
+            "--rounds" => rounds = args.next().ok_or(USAGE)?.parse()?,
+            "--max-turns" => max_turns = args.next().ok_or(USAGE)?.parse()?,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    if bots.len() < 2 {
+        return Err(USAGE.into());
+    }
+    if maps.is_empty() && generated == 0 {
+        for entry in fs::read_dir("tests")? {
+            maps.push(entry?.path().to_string_lossy().into_owned());
+        }
+        maps.sort();
+    }
+
+    let mut ratings: BTreeMap<String, Rating> = fs::read_to_string(&ratings_path)
+        .ok()
+        .and_then(|x| serde_json::from_str(&x).ok())
+        .unwrap_or_default();
+
+    let n = bots.len();
+    let mut wins = vec![vec![0.; n]; n];
+    let mut games = vec![vec![0; n]; n];
+
+    let mut pool = Vec::new();
+    for path in maps {
+        pool.push((load_map(&path)?, path));
+    }
+    let families = [Family::Hex, Family::Spiral, Family::Scatter];
+    for seed in 0..generated {
+        let family = families[seed as usize % families.len()];
+        let options = MapOptions {
+            family,
+            planets: 13 + 2 * (seed as usize % 5),
+            spread: 20.,
+            neutral_ships: 20,
+            seed,
+            ..MapOptions::default()
+        };
+        pool.push((generate(&options)?, format!("{:?} #{}", family, seed)));
+    }
+
+    for (map, path) in &pool {
+        if Game::new(map.clone(), max_turns).players() != 2 {
+            eprintln!("Skipping {}, it is not a two player map", path);
+            continue;
+        }
+
+        for _ in 0..rounds {
+            for a in 0..n {
+                for b in (0..n).filter(|&b| b != a) {
+                    // Every ordered pair, so both seat orders are played
+                    let winner = play(map, [&bots[a], &bots[b]], max_turns)?;
+                    let score = match winner {
+                        Some(0) => 1.,
+                        Some(_) => 0.,
+                        None => 0.5,
+                    };
+                    println!(
+                        "{}: {} vs {} -> {}",
+                        path,
+                        bots[a].name,
+                        bots[b].name,
+                        match winner {
+                            Some(seat) => &bots[[a, b][seat]].name,
+                            None => "draw",
+                        }
+                    );
+
+                    wins[a][b] += score;
+                    wins[b][a] += 1. - score;
+                    games[a][b] += 1;
+                    games[b][a] += 1;
+                    update_elo(&mut ratings, &bots[a].name, &bots[b].name, score);
+                }
+            }
+        }
+    }
+
+    fs::write(&ratings_path, serde_json::to_string_pretty(&ratings)?)?;
+
+    let width = bots.iter().map(|b| b.name.len()).max().unwrap().max(6);
+    println!();
+    print!("{:width$}", "");
+    for bot in &bots {
+        print!(" {:>width$}", bot.name);
+    }
+    println!();
+    for (a, bot) in bots.iter().enumerate() {
+        print!("{:width$}", bot.name);
+        for b in 0..n {
+            if games[a][b] == 0 {
+                print!(" {:>width$}", "-");
+            } else {
+                let rate = format!("{:.0}%", 100. * wins[a][b] / games[a][b] as f64);
+                print!(" {:>width$}", rate);
+            }
+        }
+        println!();
+    }
+
+    println!();
+    for (name, rating) in &ratings {
+        println!(
+            "{:width$} {:>7.1} ({} games)",
+            name, rating.elo, rating.games
+        );
+    }
+
+    Ok(())
+}
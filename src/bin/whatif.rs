@@ -0,0 +1,158 @@
+use std::{collections::BTreeSet, env, error::Error, fs, sync::atomic::AtomicBool, time::Instant};
+
+use silviator::{best_planet, chosen_dot, models::*};
+
+const USAGE: &str =
+    "usage: whatif <input file> [--snapshot] [--save FILE] [--turn N] [--horizon H] [--explain] \
+                     [--dot FILE] \
+                     <origin>:<destination>:<ships>...";
+
+struct Move {
+    source: usize,
+    target: usize,
+    ships: i32,
+}
+
+fn parse_move(state: &State, arg: &str) -> Result<Move, String> {
+    let parts: Vec<_> = arg.rsplitn(3, ':').collect();
+    let [ships, destination, origin] = parts[..] else {
+        return Err(format!(
+            "Move {:?} is not formatted as origin:destination:ships",
+            arg
+        ));
+    };
+
+    let planet = |name: &str| {
+        state
+            .planet_id(name)
+            .ok_or_else(|| format!("Unknown planet {:?}", name))
+    };
+
+    let (source, target) = (planet(origin)?, planet(destination)?);
+    if source == target {
+        return Err(format!("Move {:?} sends ships to their origin", arg));
+    }
+
+    Ok(Move {
+        source,
+        target,
+        ships: ships
+            .parse()
+            .map_err(|_| format!("Invalid ship count {:?}", ships))?,
+    })
+}
+
+/// Replays the recorded turns up to and including `turn`, the last line when not given
+fn load_state(content: &str, turn: Option<usize>) -> Result<State, Box<dyn Error>> {
+    let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+    let last = turn.unwrap_or(usize::MAX);
+
+    let first = lines.next().ok_or("Input file is empty")?;
+    let mut state = State::new(serde_json::from_str::<Input>(first)?);
+
+    for line in lines.take(last) {
+        state.turn(serde_json::from_str::<Input>(line)?);
+    }
+
+    Ok(state)
+}
+
+fn fmt_planet(p: &Planet) -> String {
+    format!("{:>3} ships (owner {})", p.ships, p.owner)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+
+    let mut file = None;
+    let mut snapshot = false;
+    let mut explain = false;
+    let mut save = None;
+    let mut dot = None;
+    let mut turn = None;
+    let mut horizon = None;
+    let mut raw_moves = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--turn" => turn = Some(args.next().ok_or(USAGE)?.parse()?),
+            "--horizon" => horizon = Some(args.next().ok_or(USAGE)?.parse()?),
+            "--snapshot" => snapshot = true,
+            "--explain" => explain = true,
+            "--save" => save = Some(args.next().ok_or(USAGE)?),
+            "--dot" => dot = Some(args.next().ok_or(USAGE)?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if file.is_none() => file = Some(arg),
+            _ => raw_moves.push(arg),
+        }
+    }
+
+    let content = fs::read_to_string(file.ok_or(USAGE)?)?;
+    let base = if snapshot {
+        State::from_snapshot(&content)?
+    } else {
+        load_state(&content, turn)?
+    };
+    let mut state = State::from_snapshot(&base.snapshot())?;
+
+    if let Some(save) = save {
+        fs::write(save, base.snapshot())?;
+    }
+
+    if let Some(dot) = dot {
+        fs::write(dot, chosen_dot(&base))?;
+    }
+
+    if explain {
+        let mut planner = base.clone();
+        for explanation in best_planet(&mut planner, Instant::now(), &AtomicBool::new(false)) {
+            println!("{}", explanation.describe(&base));
+        }
+        for (source, target, ships) in planner.take_moves() {
+            println!(
+                "Sending {} ships {} -> {}",
+                ships, base.inv_planet_map[source], base.inv_planet_map[target]
+            );
+        }
+    }
+
+    let moves = raw_moves
+        .iter()
+        .map(|m| parse_move(&state, m))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut affected = BTreeSet::new();
+    for m in &moves {
+        state.apply_move(m.source, m.target, m.ships);
+        affected.insert(m.source);
+        affected.insert(m.target);
+    }
+
+    for id in affected {
+        let before = &base.planets()[id];
+        let after = &state.planets()[id];
+
+        println!("{}", state.inv_planet_map[id]);
+        println!("  turn  {:<24}  with moves", "without moves");
+        for (i, (b, a)) in before
+            .futures()
+            .zip(after.futures())
+            .take(horizon.unwrap_or(usize::MAX))
+            .enumerate()
+        {
+            let marker = if b != a { "*" } else { " " };
+            println!(
+                "  {:>4}  {:<24}{} {}",
+                i,
+                fmt_planet(b),
+                marker,
+                fmt_planet(a)
+            );
+        }
+    }
+
+    Ok(())
+}
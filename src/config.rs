@@ -0,0 +1,144 @@
+use std::{env, str::FromStr, thread};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::{CombatRule, GameRules},
+    value::Scoring,
+};
+
+/// Knobs of the planner, read from `SILVIATOR_*` environment variables
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Worker threads evaluating candidate operations
+    pub threads: usize,
+    /// Milliseconds into the turn after which no new orderings are tried
+    pub search_ms: u64,
+    /// Share of the worst case enemy threat that planets ignore when offering ships,
+    /// 0 keeps every planet safe from all garrisons in range, 1 ignores them.
+    /// That worst case has every enemy planet empty itself onto a single planet of ours,
+    /// the default of 0.5 still covers a planet against half of it.
+    pub risk: f32,
+    /// Extra value for enemy planets that just sent their garrison away,
+    /// a planet that emptied itself completely weighs `1 + counter_attack` times as much
+    pub counter_attack: f32,
+    /// How much a capture we cannot hold against the threat after landing is discounted,
+    /// 0 ignores holding, 1 leaves no value to a garrison that covers none of it
+    pub hold: f32,
+    /// Share of the ships enemy planets can bring to a target in time that attacks plan for
+    pub reinforce: f32,
+    /// Turns the server plays before the biggest fleet wins
+    pub game_length: usize,
+    /// How operations are ranked from the value of their target
+    pub scoring: Scoring,
+    /// Turns predicted ahead, `None` looks as far as the map is wide.
+    /// Fleets landing later make it grow on demand.
+    pub horizon: Option<usize>,
+    /// Rules of the server, the predictions assume these
+    pub rules: GameRules,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(1, |x| x.get()),
+            search_ms: 800,
+            risk: 0.5,
+            counter_attack: 1.,
+            hold: 0.5,
+            reinforce: 0.5,
+            game_length: 500,
+            scoring: Scoring::Squared,
+            horizon: None,
+            rules: GameRules::default(),
+        }
+    }
+}
+
+fn var<T: FromStr>(lookup: &impl Fn(&str) -> Option<String>, name: &str) -> Option<T> {
+    lookup(name)?.parse().ok()
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// Reads the knobs from `lookup`, values that are missing or do not parse keep their default
+    pub fn from_vars(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let mut config = Self::default();
+
+        if let Some(threads) = var(&lookup, "SILVIATOR_THREADS") {
+            config.threads = threads;
+        }
+        if let Some(search_ms) = var(&lookup, "SILVIATOR_SEARCH_MS") {
+            config.search_ms = search_ms;
+        }
+        if let Some(risk) = var::<f32>(&lookup, "SILVIATOR_RISK") {
+            config.risk = risk.clamp(0., 1.);
+        }
+        if let Some(counter_attack) = var(&lookup, "SILVIATOR_COUNTER_ATTACK") {
+            config.counter_attack = counter_attack;
+        }
+
+        if let Some(hold) = var::<f32>(&lookup, "SILVIATOR_HOLD") {
+            config.hold = hold.clamp(0., 1.);
+        }
+
+        if let Some(reinforce) = var::<f32>(&lookup, "SILVIATOR_REINFORCE") {
+            config.reinforce = reinforce.max(0.);
+        }
+        if let Some(game_length) = var(&lookup, "SILVIATOR_GAME_LENGTH") {
+            config.game_length = game_length;
+        }
+        if let Some(scoring) = var(&lookup, "SILVIATOR_SCORING") {
+            config.scoring = scoring;
+        }
+
+        if let Some(horizon) = var(&lookup, "SILVIATOR_HORIZON") {
+            config.horizon = Some(horizon);
+        }
+        if let Some(travel) = var(&lookup, "SILVIATOR_ROUNDING") {
+            config.rules.travel = travel;
+        }
+        if let Some(growth) = var(&lookup, "SILVIATOR_GROWTH") {
+            config.rules.growth = Some(growth);
+        }
+        if let Some(ships) = var(&lookup, "SILVIATOR_NEUTRAL_BONUS") {
+            config.rules.combat = CombatRule::NeutralBonus { ships };
+        }
+
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::Config;
+    use crate::{models::CombatRule, value::Scoring};
+
+    #[test]
+    fn reads_variables() {
+        let vars: HashMap<_, _> = [
+            ("SILVIATOR_THREADS", "3"),
+            ("SILVIATOR_SEARCH_MS", "300"),
+            ("SILVIATOR_RISK", "2"),
+            ("SILVIATOR_HOLD", "half"),
+            ("SILVIATOR_SCORING", "rate"),
+            ("SILVIATOR_NEUTRAL_BONUS", "4"),
+        ]
+        .into_iter()
+        .collect();
+        let config = Config::from_vars(|name| vars.get(name).map(|x| x.to_string()));
+
+        assert_eq!(config.threads, 3);
+        assert_eq!(config.search_ms, 300);
+        assert_eq!(config.risk, 1.);
+        assert_eq!(config.hold, Config::default().hold);
+        assert_eq!(config.scoring, Scoring::Rate);
+        assert_eq!(config.rules.combat, CombatRule::NeutralBonus { ships: 4 });
+    }
+}
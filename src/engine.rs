@@ -0,0 +1,343 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Combat, GameRules, Owner, NEUTRAL};
+
+fn default_growth() -> i32 {
+    1
+}
+
+fn is_default_growth(growth: &i32) -> bool {
+    *growth == 1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Planet {
+    pub ship_count: i32,
+    pub x: f32,
+    pub y: f32,
+    pub owner: Option<Owner>,
+    pub name: String,
+    #[serde(default = "default_growth", skip_serializing_if = "is_default_growth")]
+    pub growth: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Expedition {
+    pub id: u64,
+    pub ship_count: i32,
+    pub origin: String,
+    pub destination: String,
+    pub owner: Owner,
+    pub turns_remaining: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Map {
+    pub planets: Vec<Planet>,
+    #[serde(default)]
+    pub expeditions: Vec<Expedition>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Move {
+    pub origin: String,
+    pub destination: String,
+    pub ship_count: i32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Moves {
+    pub moves: Vec<Move>,
+}
+
+/// How a game ended, `None` is a draw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Running,
+    Finished(Option<Owner>),
+}
+
+/// Referee for the json protocol, players are numbered from 1 like the owners of the map
+#[derive(Debug, Clone)]
+pub struct Game {
+    map: Map,
+    players: usize,
+    next_id: u64,
+    pub turn: usize,
+    pub max_turns: usize,
+    pub rules: GameRules,
+}
+
+impl Game {
+    /// Expeditions that have already landed are dropped from `map`
+    pub fn new(mut map: Map, max_turns: usize) -> Self {
+        map.expeditions.retain(|e| e.turns_remaining > 0);
+        let players = map
+            .planets
+            .iter()
+            .filter_map(|p| p.owner)
+            .chain(map.expeditions.iter().map(|e| e.owner))
+            .max()
+            .unwrap_or(0);
+        let next_id = map.expeditions.iter().map(|e| e.id + 1).max().unwrap_or(0);
+
+        Self {
+            map,
+            players,
+            next_id,
+            turn: 0,
+            max_turns,
+            rules: GameRules::default(),
+        }
+    }
+
+    pub fn players(&self) -> usize {
+        self.players
+    }
+
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    /// The map as `player` sees it, every player plays as owner 1
+    pub fn view(&self, player: Owner) -> String {
+        let swap = |owner: Owner| {
+            if owner == player {
+                1
+            } else if owner == 1 {
+                player
+            } else {
+                owner
+            }
+        };
+
+        let mut map = self.map.clone();
+        for p in &mut map.planets {
+            p.owner = p.owner.map(swap);
+        }
+        for e in &mut map.expeditions {
+            e.owner = swap(e.owner);
+        }
+
+        serde_json::to_string(&map).unwrap()
+    }
+
+    fn planet(&self, name: &str) -> Option<usize> {
+        self.map.planets.iter().position(|p| p.name == name)
+    }
+
+    /// Dispatches the moves of `player`, invalid ones are dropped
+    pub fn dispatch(&mut self, player: Owner, moves: &[Move]) {
+        for m in moves {
+            let (Some(origin), Some(destination)) =
+                (self.planet(&m.origin), self.planet(&m.destination))
+            else {
+                continue;
+            };
+
+            let (source, target) = (&self.map.planets[origin], &self.map.planets[destination]);
+            if source.owner != Some(player)
+                || origin == destination
+                || m.ship_count <= 0
+                || m.ship_count > source.ship_count
+            {
+                continue;
+            }
+            let distance = self
+                .rules
+                .travel_time((source.x - target.x).hypot(source.y - target.y));
+            self.map.planets[origin].ship_count -= m.ship_count;
+
+            self.map.expeditions.push(Expedition {
+                id: self.next_id,
+                ship_count: m.ship_count,
+                origin: m.origin.clone(),
+                destination: m.destination.clone(),
+                owner: player,
+                turns_remaining: distance,
+            });
+            self.next_id += 1;
+        }
+    }
+
+    /// Grows the planets, moves the expeditions and fights over the planets they reach
+    pub fn step(&mut self) {
+        for p in &mut self.map.planets {
+            if p.owner.is_some_and(|o| o != NEUTRAL) {
+                p.ship_count += self.rules.planet_growth(p.growth);
+            }
+        }
+
+        let mut arrivals = vec![vec![0; self.players + 1]; self.map.planets.len()];
+        let mut flying = Vec::new();
+        for mut e in std::mem::take(&mut self.map.expeditions) {
+            e.turns_remaining -= 1;
+            if e.turns_remaining > 0 {
+                flying.push(e);
+            } else if let Some(target) = self.planet(&e.destination) {
+                arrivals[target][e.owner] += e.ship_count;
+            }
+        }
+        self.map.expeditions = flying;
+
+        for (p, arriving) in self.map.planets.iter_mut().zip(arrivals) {
+            if arriving.iter().all(|&x| x == 0) {
+                continue;
+            }
+
+            let owner = p.owner.unwrap_or(NEUTRAL);
+            let mut forces = arriving;
+            forces[owner] += p.ship_count;
+
+            let (winner, ships) = self.rules.combat.resolve(owner, &forces);
+            p.owner = (winner != NEUTRAL).then_some(winner);
+            p.ship_count = ships;
+        }
+
+        self.turn += 1;
+    }
+
+    /// Ships on planets and in flight per player, index 0 is neutral
+    pub fn ships(&self) -> Vec<i32> {
+        let mut ships = vec![0; self.players + 1];
+        for p in &self.map.planets {
+            ships[p.owner.unwrap_or(NEUTRAL)] += p.ship_count;
+        }
+        for e in &self.map.expeditions {
+            ships[e.owner] += e.ship_count;
+        }
+        ships
+    }
+
+    /// A player is out without ships, after `max_turns` the biggest fleet wins
+    pub fn outcome(&self) -> Outcome {
+        let ships = self.ships();
+        let alive: Vec<_> = (1..=self.players).filter(|&p| ships[p] > 0).collect();
+
+        match alive[..] {
+            [] => Outcome::Finished(None),
+            [winner] => Outcome::Finished(Some(winner)),
+            _ if self.turn >= self.max_turns => {
+                let best = alive.iter().map(|&p| ships[p]).max().unwrap();
+                let leaders: Vec<_> = alive.into_iter().filter(|&p| ships[p] == best).collect();
+                Outcome::Finished((leaders.len() == 1).then(|| leaders[0]))
+            }
+            _ => Outcome::Running,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Game, Map, Move, Outcome};
+    use crate::{
+        config::Config,
+        models::{recorded::*, *},
+    };
+
+    fn hex() -> Game {
+        let line = HEX.lines().next().unwrap();
+        Game::new(serde_json::from_str::<Map>(line).unwrap(), 100)
+    }
+
+    fn send(origin: &str, destination: &str, ship_count: i32) -> Move {
+        Move {
+            origin: origin.to_string(),
+            destination: destination.to_string(),
+            ship_count,
+        }
+    }
+
+    #[test]
+    fn replays_the_recorded_second_turn() {
+        let mut game = hex();
+        game.dispatch(2, &[send("tetartos", "protos", 5)]);
+        // Player 2 does not own protos
+        game.dispatch(2, &[send("protos", "duteros", 5)]);
+        assert_eq!(game.map().expeditions.len(), 1);
+        game.step();
+
+        let recorded = HEX.lines().nth(1).unwrap();
+        assert_eq!(game.view(1), recorded);
+        assert!(game.view(2).contains(r#""owner":1,"name":"tetartos""#));
+    }
+
+    #[test]
+    fn capture_and_knockout() {
+        let map = r#"{"planets":[
+            {"ship_count":9,"x":0,"y":0,"owner":1,"name":"a"},
+            {"ship_count":3,"x":2,"y":0,"owner":2,"name":"b"}
+        ]}"#;
+        let mut game = Game::new(serde_json::from_str(map).unwrap(), 100);
+        game.dispatch(1, &[send("a", "b", 8), send("a", "b", 5)]);
+        assert_eq!(game.map().expeditions.len(), 1);
+
+        game.step();
+        assert_eq!(game.outcome(), Outcome::Running);
+        game.step();
+
+        // 8 attackers meet 3 + 2 grown defenders
+        assert_eq!(game.map().planets[1].owner, Some(1));
+        assert_eq!(game.map().planets[1].ship_count, 3);
+        assert_eq!(game.outcome(), Outcome::Finished(Some(1)));
+    }
+
+    #[test]
+    fn accepts_odd_expeditions() {
+        let map = r#"{"planets":[
+            {"ship_count":9,"x":0,"y":0,"owner":1,"name":"a"},
+            {"ship_count":3,"x":2,"y":0,"owner":2,"name":"b"}
+        ],"expeditions":[
+            {"id":0,"ship_count":4,"origin":"a","destination":"b","owner":1,"turns_remaining":0},
+            {"id":1,"ship_count":12,"origin":"b","destination":"a","owner":5,"turns_remaining":1}
+        ]}"#;
+        let mut game = Game::new(serde_json::from_str(map).unwrap(), 100);
+        assert_eq!(game.map().expeditions.len(), 1);
+        assert_eq!(game.players(), 5);
+
+        game.step();
+        assert_eq!(game.map().planets[0].owner, Some(5));
+    }
+
+    #[test]
+    fn predictions_follow_the_rules() {
+        let map = r#"{"planets":[
+            {"ship_count":20,"x":0,"y":0,"owner":1,"name":"a"},
+            {"ship_count":5,"x":2.4,"y":0,"owner":null,"name":"n"},
+            {"ship_count":20,"x":9,"y":0,"owner":2,"name":"b"}
+        ]}"#;
+        let rules = GameRules {
+            travel: Rounding::Round,
+            growth: Some(2),
+            combat: CombatRule::NeutralBonus { ships: 3 },
+        };
+
+        for ships in [7, 8, 9, 12] {
+            let mut game = Game::new(serde_json::from_str(map).unwrap(), 100);
+            game.rules = rules;
+            let config = Config {
+                rules,
+                ..Config::default()
+            };
+            let mut state =
+                State::with_config(serde_json::from_str(&game.view(1)).unwrap(), config);
+
+            // Seen like the bot sees it, one turn after the launch
+            game.dispatch(1, &[send("a", "n", ships), send("b", "n", 4)]);
+            game.step();
+            state.turn(serde_json::from_str(&game.view(1)).unwrap());
+            for turn in 0..8 {
+                let predicted = state.planets()[1][turn];
+                let planet = &game.map().planets[1];
+                assert_eq!(
+                    (predicted.owner, predicted.ships),
+                    (planet.owner.unwrap_or(NEUTRAL), planet.ship_count),
+                    "{} ships, turn {}",
+                    ships,
+                    turn
+                );
+                game.step();
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::{models::State, OptionalOperation};
+
+/// Ships a planet offered to an operation, and how many the flow actually took
+#[derive(Debug, Clone, Serialize)]
+pub struct Offer {
+    pub planet: usize,
+    pub dist: usize,
+    pub usable_ships: i32,
+    pub sent: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Verdict {
+    Accepted,
+    /// The target is ours, or will be, at every distance we can reach it
+    AlreadyOurs,
+    /// None of our planets can spare ships for this target
+    NoSourceShips,
+    /// Our planets have ships, but never enough before the prediction horizon
+    NoTime {
+        best_offer: i32,
+    },
+    /// The flow could not deliver the `wanted` ships to the target
+    UnsatisfiedWanted {
+        wanted: i32,
+        flow: i32,
+    },
+    /// A contributing planet would drop below what it needs itself
+    PlanetRequirement {
+        planet: usize,
+    },
+    /// Capturing it costs more ships than it makes before the game ends
+    NotWorth,
+    /// An earlier plan is already underway against this target
+    Planned,
+    /// Accepted, but simulating the sends did not capture it or cost us a planet
+    Unverified,
+    /// Tried, but adding it to the chosen operations did not raise the total score
+    NoImprovement,
+    /// The deadline hit before this candidate was tried
+    NotEvaluated,
+}
+
+/// Why the planner did or did not attack `target`
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+    pub target: usize,
+    pub duration: Option<usize>,
+    pub required_ships: i32,
+    pub value: f32,
+    pub score: Option<f32>,
+    pub offers: Vec<Offer>,
+    pub verdict: Verdict,
+}
+
+impl Explanation {
+    pub fn candidate(op: &OptionalOperation, verdict: Verdict) -> Self {
+        Self {
+            target: op.target,
+            duration: Some(op.duration),
+            required_ships: op.required_ships,
+            value: op.value,
+            score: Some(op.score()),
+            offers: op
+                .usable_planets
+                .iter()
+                .map(|p| Offer {
+                    planet: p.id,
+                    dist: p.dist,
+                    usable_ships: p.usable_ships,
+                    sent: None,
+                })
+                .collect(),
+            verdict,
+        }
+    }
+
+    pub fn describe(&self, state: &State) -> String {
+        let name = |id: usize| &state.inv_planet_map[id];
+
+        let mut out = format!("{} {:?}", name(self.target), self.verdict);
+        if let Some(duration) = self.duration {
+            let _ = write!(
+                out,
+                " (in {} turns, {} ships, value {}",
+                duration, self.required_ships, self.value
+            );
+            if let Some(score) = self.score {
+                let _ = write!(out, ", score {}", score);
+            }
+            out.push(')');
+        }
+        for offer in &self.offers {
+            let _ = write!(
+                out,
+                "\n    {} at {} offers {}",
+                name(offer.planet),
+                offer.dist,
+                offer.usable_ships
+            );
+            if let Some(sent) = offer.sent {
+                let _ = write!(out, ", sends {}", sent);
+            }
+        }
+        out
+    }
+}
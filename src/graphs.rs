@@ -10,7 +10,11 @@ use rs_graph::{
     Buildable, Builder, LinkedListGraph,
 };
 
-use crate::{models::State, OptionalOperation, UsablePlanet};
+use crate::{
+    explain::{Explanation, Verdict},
+    models::State,
+    usable_ships, OptionalOperation, UsablePlanet,
+};
 
 type NodeId = g::Node<usize>;
 type EdgeId = g::Edge<usize>;
@@ -18,6 +22,8 @@ type EdgeId = g::Edge<usize>;
 #[derive(Debug)]
 pub struct Edge {
     wanted: Option<i32>,
+    from: NodeId,
+    to: NodeId,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -31,7 +37,8 @@ struct PlanetNodeRef {
     id: usize,
     planet: usize,
     edge: EdgeId,
-    future: bool,
+    /// Turns the planet waits before sending, to arrive together with the others
+    delay: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -42,9 +49,9 @@ pub struct OperationNode<'b> {
     pub optional_operation: &'b OptionalOperation,
 }
 
-impl<'a> Into<Node<'a>> for OperationNode<'a> {
-    fn into(self) -> Node<'a> {
-        Node::Operation(self)
+impl<'a> From<OperationNode<'a>> for Node<'a> {
+    fn from(node: OperationNode<'a>) -> Self {
+        Node::Operation(node)
     }
 }
 
@@ -54,12 +61,13 @@ pub struct PlanetNode {
     required: Vec<EdgeId>,
 }
 
-impl<'a> Into<Node<'a>> for PlanetNode {
-    fn into(self) -> Node<'a> {
-        Node::Planet(self)
+impl<'a> From<PlanetNode> for Node<'a> {
+    fn from(node: PlanetNode) -> Self {
+        Node::Planet(node)
     }
 }
 
+#[allow(unused)]
 #[derive(Debug, Clone)]
 pub enum Node<'a> {
     Special(Type),
@@ -158,7 +166,12 @@ impl<'a, 'b> NodeOrchestrator<'a, 'b> {
             blank_edge,
             destination,
             state,
-            edges: vec![Edge { wanted: None }],
+            // The blank edge only fills unused slots, it should never carry flow
+            edges: vec![Edge {
+                wanted: Some(0),
+                from: source,
+                to: destination,
+            }],
             nodes: vec![
                 Node::Special(Type::Source),
                 Node::Special(Type::Destination),
@@ -174,7 +187,11 @@ impl<'a, 'b> NodeOrchestrator<'a, 'b> {
     }
 
     fn add_edge(&mut self, source: NodeId, target: NodeId, wanted: Option<i32>) -> EdgeId {
-        let edge = Edge { wanted };
+        let edge = Edge {
+            wanted,
+            from: source,
+            to: target,
+        };
         self.edges.push(edge);
         self.builder.add_edge(source, target)
     }
@@ -188,8 +205,10 @@ impl<'a, 'b> NodeOrchestrator<'a, 'b> {
             // Spawn planet
 
             if i == 0 {
+                // The chain starts from what the planet spares now, later nodes add its growth
                 let node = PlanetNode::default();
-                let count = usable_planet.usable_ships;
+                let count =
+                    usable_ships(&planets[planet_id], 0, self.state).map_or(0, |x| x.max(0));
                 let planet_node = self.add_node(node);
                 self.planets[planet_id].push(planet_node);
 
@@ -216,7 +235,7 @@ impl<'a, 'b> NodeOrchestrator<'a, 'b> {
                 // You are losing ships mate
                 let req = if delta < 0 {
                     self.add_edge(self.source, planet_node, Some(0));
-                    self.add_edge(planet_node, self.destination, Some(-1 * delta))
+                    self.add_edge(planet_node, self.destination, Some(-delta))
                 } else {
                     self.add_edge(self.source, planet_node, Some(delta));
                     self.add_edge(planet_node, self.destination, Some(0))
@@ -250,23 +269,87 @@ impl<'a, 'b> NodeOrchestrator<'a, 'b> {
                 id,
                 planet: usable_planet.id,
                 edge: incoming,
-                future: op.optional_operation.duration != usable_planet.dist,
+                delay: op.optional_operation.duration - usable_planet.dist,
             });
         }
     }
 
-    fn solve(self) -> Vec<Operation> {
-        let graph = self.builder.into_graph();
+    /// Runs the max flow, the graph can not be extended afterwards
+    fn max_flow(&mut self) -> HashMap<EdgeId, i32> {
+        let builder = std::mem::replace(&mut self.builder, LinkedListGraph::new_builder());
+        let graph = builder.into_graph();
 
         let (_, edges, _) = edmondskarp(&graph, self.source, self.destination, |e| {
             self.edges[e.index()].wanted.unwrap_or(i32::MAX)
         });
 
+        edges.iter().map(|(e, &i)| (e, i)).collect()
+    }
+
+    fn to_dot(&self, edge_amounts: &HashMap<EdgeId, i32>) -> String {
+        let name = |planet: usize| &self.state.inv_planet_map[planet];
+
+        let mut planet_nodes = HashMap::new();
+        for (planet, nodes) in self.planets.iter().enumerate() {
+            for (turn, node) in nodes.iter().enumerate() {
+                planet_nodes.insert(node.index(), (planet, turn));
+            }
+        }
+
+        let mut out = String::from("digraph flow {\n    rankdir=LR;\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let (label, shape) = match node {
+                Node::Special(Type::Source) => ("source".to_string(), "doublecircle"),
+                Node::Special(Type::Destination) => ("sink".to_string(), "doublecircle"),
+                Node::Operation(op) => {
+                    let op = op.optional_operation;
+                    let label = format!(
+                        "attack {}\\nin {} turns\\nneeds {}",
+                        name(op.target),
+                        op.duration,
+                        op.required_ships
+                    );
+                    (label, "box")
+                }
+                Node::Planet(_) => {
+                    let (planet, turn) = planet_nodes[&i];
+                    (format!("{}\\nturn {}", name(planet), turn), "ellipse")
+                }
+            };
+            out += &format!("    n{} [label=\"{}\", shape={}];\n", i, label, shape);
+        }
+
+        let mut flows = vec![0; self.edges.len()];
+        for (e, &flow) in edge_amounts {
+            flows[e.index()] = flow;
+        }
+
+        for (edge, &flow) in self.edges.iter().zip(&flows).skip(1) {
+            let (capacity, color) = match edge.wanted {
+                Some(wanted) if wanted != flow => (wanted.to_string(), "red"),
+                Some(wanted) => (wanted.to_string(), "black"),
+                None => ("inf".to_string(), "gray"),
+            };
+            out += &format!(
+                "    n{} -> n{} [label=\"{}/{}\", color={}];\n",
+                edge.from.index(),
+                edge.to.index(),
+                flow,
+                capacity,
+                color
+            );
+        }
+
+        out + "}\n"
+    }
+
+    fn solve(mut self) -> (Vec<Operation>, Vec<Explanation>) {
+        let edge_amounts = self.max_flow();
         let self_edges = self.edges;
 
-        let good_edges: HashSet<EdgeId> = edges
+        let good_edges: HashSet<EdgeId> = edge_amounts
             .iter()
-            .filter_map(|(e, &i)| {
+            .filter_map(|(&e, &i)| {
                 let edge = &self_edges[e.index()];
                 if let Some(wanted) = edge.wanted {
                     (i == wanted).then_some(e)
@@ -276,8 +359,6 @@ impl<'a, 'b> NodeOrchestrator<'a, 'b> {
             })
             .collect();
 
-        let edge_amounts: HashMap<EdgeId, i32> = edges.iter().map(|(e, &i)| (e, i)).collect();
-
         let good_planets: HashSet<_> = self
             .nodes
             .iter()
@@ -294,36 +375,59 @@ impl<'a, 'b> NodeOrchestrator<'a, 'b> {
             })
             .collect();
 
-        self.nodes
-            .into_iter()
-            .filter_map(|x| match x {
-                Node::Operation(p) => Some(p),
-                _ => None,
-            })
-            .filter_map(|x| {
-                if x.planets.iter().all(|node| good_planets.contains(&node.id))
-                    && good_edges.contains(&x.destination)
-                {
-                    let target = x.optional_operation.target;
-                    Some(Operation {
-                        score: x.optional_operation.score(),
-                        solution: x
-                            .planets
-                            .iter()
-                            .flat_map(|node| {
-                                (!node.future).then_some(SolutionPart {
-                                    source: node.planet,
-                                    target,
-                                    ships: edge_amounts[&node.edge],
-                                })
-                            })
-                            .collect(),
-                    })
-                } else {
-                    None
+        let mut operations = Vec::new();
+        let mut explanations = Vec::new();
+
+        for x in self.nodes.into_iter().filter_map(|x| match x {
+            Node::Operation(p) => Some(p),
+            _ => None,
+        }) {
+            let failed_planet = x
+                .planets
+                .iter()
+                .find(|node| !good_planets.contains(&node.id));
+
+            let verdict = if !good_edges.contains(&x.destination) {
+                Verdict::UnsatisfiedWanted {
+                    wanted: self_edges[x.destination.index()].wanted.unwrap_or_default(),
+                    flow: edge_amounts[&x.destination],
                 }
-            })
-            .collect()
+            } else if let Some(node) = failed_planet {
+                Verdict::PlanetRequirement {
+                    planet: node.planet,
+                }
+            } else {
+                Verdict::Accepted
+            };
+
+            let mut explanation = Explanation::candidate(x.optional_operation, verdict);
+            for (offer, node) in explanation.offers.iter_mut().zip(&x.planets) {
+                offer.sent = Some(edge_amounts[&node.edge]);
+            }
+
+            if explanation.verdict == Verdict::Accepted {
+                let target = x.optional_operation.target;
+                operations.push(Operation {
+                    score: x.optional_operation.score(),
+                    target,
+                    arrival: x.optional_operation.duration,
+                    solution: x
+                        .planets
+                        .iter()
+                        .map(|node| SolutionPart {
+                            source: node.planet,
+                            target,
+                            ships: edge_amounts[&node.edge],
+                            delay: node.delay,
+                        })
+                        .filter(|part| part.ships > 0)
+                        .collect(),
+                });
+            }
+            explanations.push(explanation);
+        }
+
+        (operations, explanations)
     }
 }
 
@@ -331,14 +435,22 @@ pub struct SolutionPart {
     pub source: usize,
     pub target: usize,
     pub ships: i32,
+    pub delay: usize,
 }
 
 pub struct Operation {
     pub score: f32,
+    pub target: usize,
+    pub arrival: usize,
     pub solution: Vec<SolutionPart>,
 }
 
-pub fn try_oo<'a, 'b>(operations: &'b [OptionalOperation], state: &'a State) -> Vec<Operation> {
+/// Solves the flow for `operations`, returns the accepted ones and for every operation
+/// why it was accepted or dropped
+pub fn try_oo<'b>(
+    operations: impl IntoIterator<Item = &'b OptionalOperation>,
+    state: &State,
+) -> (Vec<Operation>, Vec<Explanation>) {
     let mut orchestrator = NodeOrchestrator::new(state);
 
     for op in operations {
@@ -347,3 +459,41 @@ pub fn try_oo<'a, 'b>(operations: &'b [OptionalOperation], state: &'a State) ->
 
     orchestrator.solve()
 }
+
+/// Renders the flow network of `operations` as Graphviz dot, edges are labeled with
+/// `flow/capacity` and colored red when they do not carry exactly the wanted flow
+pub fn oo_to_dot<'b>(
+    operations: impl IntoIterator<Item = &'b OptionalOperation>,
+    state: &State,
+) -> String {
+    let mut orchestrator = NodeOrchestrator::new(state);
+
+    for op in operations {
+        orchestrator.add_optional_operation(op);
+    }
+
+    let edge_amounts = orchestrator.max_flow();
+    orchestrator.to_dot(&edge_amounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{candidates, graphs::oo_to_dot, models::recorded::*};
+
+    #[test]
+    fn dot_has_every_node_and_edge() {
+        let state = state_at(HEX, 0);
+        let (candidates, _) = candidates(&state);
+        let dot = oo_to_dot(&candidates, &state);
+
+        assert!(dot.starts_with("digraph flow {"));
+        assert!(dot.contains("[label=\"source\""));
+        assert!(dot.contains("[label=\"sink\""));
+        assert_eq!(
+            dot.matches("shape=box").count(),
+            candidates.len(),
+            "one node per operation"
+        );
+        assert!(dot.contains(" -> "));
+    }
+}
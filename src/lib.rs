@@ -0,0 +1,1150 @@
+#![feature(fn_traits)]
+#![feature(unboxed_closures)]
+use std::{
+    any::Any,
+    collections::BinaryHeap,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use graphs::Operation;
+
+use crate::{
+    explain::{Explanation, Offer, Verdict},
+    graphs::{oo_to_dot, try_oo},
+    models::*,
+    opening::{opening, OPENING_HORIZON},
+    snipe::{find_snipe, snipe_risks},
+    trace::{Trace, TraceEvent},
+    value::planet_value,
+};
+
+pub mod config;
+pub mod engine;
+pub mod explain;
+pub mod graphs;
+pub mod mapgen;
+pub mod models;
+pub mod opening;
+pub mod snipe;
+pub mod stats;
+pub mod trace;
+pub mod value;
+
+/// Time after which the turn falls back to `simple_turn`, the server allows a second
+pub const TURN_DEADLINE: Duration = Duration::from_millis(950);
+
+pub fn simple_turn(state: &mut State) -> Option<()> {
+    let current_planets: Vec<_> = state.planets().iter().map(|p| p[0]).collect();
+
+    let friendly: Vec<_> = current_planets.iter().filter(|x| x.owner == ME).collect();
+    let enemy: Vec<_> = current_planets.iter().filter(|x| x.owner != ME).collect();
+
+    let source = friendly.iter().max_by(|x, y| x.ships.cmp(&y.ships))?;
+
+    let target = enemy.iter().min_by(|x, y| x.ships.cmp(&y.ships))?;
+
+    state.add_turn(source.id, target.id, source.ships - 1);
+
+    Some(())
+}
+
+#[derive(Debug)]
+pub struct UsablePlanet {
+    pub id: usize,
+    pub dist: usize,
+    pub usable_ships: i32,
+}
+
+#[derive(Debug)]
+pub struct OptionalOperation {
+    pub value: f32,
+    score: f32,
+    pub duration: usize,
+    pub required_ships: i32,
+    // Self id, duration, and usable ships at that moment
+    pub usable_planets: Vec<UsablePlanet>,
+    pub target: usize,
+}
+
+impl OptionalOperation {
+    /// Scored with the model of the state's config
+    pub fn new(
+        target: usize,
+        duration: usize,
+        required_ships: i32,
+        usable_planets: Vec<UsablePlanet>,
+        value: f32,
+        state: &State,
+    ) -> Self {
+        let score = state
+            .config()
+            .scoring
+            .model()
+            .score(value, duration, required_ships);
+        Self {
+            value,
+            score,
+            duration,
+            required_ships,
+            usable_planets,
+            target,
+        }
+    }
+
+    pub fn score(&self) -> f32 {
+        self.score
+    }
+}
+
+impl PartialEq for OptionalOperation {
+    fn eq(&self, other: &Self) -> bool {
+        self.target == other.target && self.duration == other.duration && self.value == other.value
+    }
+}
+
+impl Eq for OptionalOperation {}
+
+impl PartialOrd for OptionalOperation {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for OptionalOperation {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let this_score = self.score();
+        let other_score = other.score();
+
+        this_score.total_cmp(&other_score)
+    }
+}
+
+/// Ships our planet `o` can spare when they leave `departure` turns from now,
+/// `None` when it is not ours by then
+pub(crate) fn usable_ships(o: &PlanetStates, departure: usize, states: &State) -> Option<i32> {
+    if o[departure].owner != ME {
+        return None;
+    }
+
+    // Counted against the worst case, minus the share of it we are willing to risk
+    let caution = 1. - states.config().risk;
+    let required_to_survive_for_5_turns = o
+        .iter_from(departure)
+        .take(10)
+        .enumerate()
+        .map(|(i, x)| {
+            let threat = states.threats().at(o.id(), departure + i);
+            let ships = if x.owner == ME { x.ships } else { -x.ships };
+            ships - (threat as f32 * caution).ceil() as i32
+        })
+        .min()
+        .unwrap();
+
+    // Ships promised to a plan are not ours to offer
+    Some(required_to_survive_for_5_turns - 1 - states.committed(o.id()))
+}
+
+/// Ships our planets in `options`, with their distance, can spare for an arrival at `d`
+pub(crate) fn usable_planets(
+    options: &[(&PlanetStates, usize)],
+    d: usize,
+    states: &State,
+) -> Vec<UsablePlanet> {
+    options
+        .iter()
+        .filter_map(|(o, actual_dist)| {
+            let usable_ships = usable_ships(o, d - actual_dist, states)?;
+            (usable_ships >= 0).then(|| UsablePlanet {
+                id: o.id(),
+                dist: *actual_dist,
+                usable_ships,
+            })
+        })
+        .collect()
+}
+
+/// Ships the owner of `target` at `d` can land there by then once it sees our fleets.
+/// Planets that have to react sooner are less likely to, their garrison counts less.
+pub(crate) fn reinforcements(target: &PlanetStates, d: usize, states: &State) -> i32 {
+    let owner = target[d].owner;
+    if owner == ME || owner == NEUTRAL {
+        return 0;
+    }
+
+    let ships: f32 = states
+        .planets()
+        .iter()
+        .filter(|p| p.id() != target.id())
+        .filter_map(|p| {
+            // Our launch shows up next turn, theirs has to leave by `d - dist`
+            let dist = p.distance(target);
+            let window = d.checked_sub(dist).filter(|&x| x >= 1)?;
+            let garrison = p[window];
+            (garrison.owner == owner)
+                .then(|| garrison.ships.max(0) as f32 * window as f32 / d as f32)
+        })
+        .sum();
+    (ships * states.config().reinforce).ceil() as i32
+}
+
+/// Turns after a capture the garrison has to hold against the enemy in reach
+const HOLD_TURNS: usize = 10;
+
+/// Ships beyond the capture the garrison needs to keep `target` when we land at `d`.
+/// Our planets in reach keep ships back against the same garrisons, those come to the rescue.
+pub(crate) fn hold_ships(target: &PlanetStates, d: usize, states: &State) -> i32 {
+    let caution = 1. - states.config().risk;
+    let kept = |planet: usize, turn: usize| {
+        (states.threats().at(planet, turn) as f32 * caution).ceil() as i32
+    };
+
+    (1..=HOLD_TURNS)
+        .map(|k| {
+            let guard: i32 = states
+                .planets()
+                .iter()
+                .filter(|p| p.id() != target.id())
+                .filter_map(|p| {
+                    let dist = p.distance(target);
+                    if dist > k {
+                        return None;
+                    }
+                    // Rescuers leave in time to land by `d + k`
+                    let leave = (d + k - dist).min(p.horizon());
+                    let garrison = p[leave];
+                    (garrison.owner == ME).then(|| kept(p.id(), leave).min(garrison.ships).max(0))
+                })
+                .sum();
+            // One ship survives the capture, the planet grows from there
+            kept(target.id(), d + k) - k as i32 * target.planet.growth - guard
+        })
+        .max()
+        .unwrap_or(0)
+        .max(0)
+}
+
+/// What the enemy can do about our capture of `target` at `d`, each garrison counted once
+pub(crate) struct EnemyResponse {
+    /// Ships landing on `target` before we do
+    pub reinforce: i32,
+    /// Ships beyond the capture needed to keep it afterwards
+    pub hold: i32,
+}
+
+impl EnemyResponse {
+    pub(crate) fn new(target: &PlanetStates, d: usize, states: &State) -> Self {
+        let reinforce = reinforcements(target, d, states);
+        // Ships that reinforced are not there to take the planet back
+        let hold = (hold_ships(target, d, states) - reinforce).max(0);
+        Self { reinforce, hold }
+    }
+}
+
+/// Raises `required_ships` to what holds the target when `offered` covers it,
+/// otherwise discounts `value` by the share of the hold left uncovered
+pub(crate) fn hold_check(
+    required_ships: i32,
+    response: &EnemyResponse,
+    offered: i32,
+    value: f32,
+    states: &State,
+) -> (i32, f32) {
+    let hold = response.hold;
+    if offered > required_ships + hold {
+        return (required_ships + hold, value);
+    }
+
+    let covered = (offered - 1 - required_ships).max(0) as f32 / hold as f32;
+    (
+        required_ships,
+        value * (1. - states.config().hold * (1. - covered)),
+    )
+}
+
+/// The earliest feasible attack on `target`, or why there is none
+fn find_optional_operations(
+    target: &PlanetStates,
+    states: &State,
+) -> Result<OptionalOperation, Explanation> {
+    let weakness = states.weakness(target.id());
+    let bonus = 1. + states.config().counter_attack * weakness;
+    let mut distances: Vec<Vec<(&PlanetStates, usize)>> = Vec::new();
+
+    for p in states.planets() {
+        let d = p.distance(target);
+        // Nothing is known about arrivals past the horizon
+        if d > target.horizon() {
+            continue;
+        }
+        while distances.len() <= d {
+            distances.push(Vec::new());
+        }
+        distances[d].push((p, d));
+    }
+
+    let mut rejected = Explanation {
+        target: target.id(),
+        duration: None,
+        required_ships: 0,
+        value: 0.,
+        score: None,
+        offers: Vec::new(),
+        verdict: Verdict::AlreadyOurs,
+    };
+
+    let mut options = Vec::new();
+    for (d, extra_options) in distances.into_iter().enumerate() {
+        if extra_options.is_empty() {
+            continue;
+        }
+
+        options.extend(extra_options);
+
+        if target.iter_from(d).any(|x| x.owner == ME) {
+            continue;
+        }
+
+        let response = EnemyResponse::new(target, d, states);
+        let required_ships = target[d].ships + 1 + response.reinforce;
+        let usable_planets = usable_planets(&options, d, states);
+
+        let value = planet_value(target, target[d].owner, d, required_ships, states) * bonus;
+        let offered = usable_planets.iter().map(|x| x.usable_ships).sum::<i32>();
+        if offered > required_ships {
+            let (required_ships, value) =
+                hold_check(required_ships, &response, offered, value, states);
+            let op = OptionalOperation::new(
+                target.id(),
+                d,
+                required_ships,
+                usable_planets,
+                value,
+                states,
+            );
+            // Later captures cost more and produce for fewer turns
+            if value <= 0. {
+                return Err(Explanation::candidate(&op, Verdict::NotWorth));
+            }
+            return Ok(op);
+        }
+
+        // Explain the distance where we came closest
+        if let Verdict::NoTime { best_offer } = rejected.verdict {
+            if best_offer >= offered {
+                continue;
+            }
+        }
+
+        rejected.duration = Some(d);
+        rejected.required_ships = required_ships;
+        rejected.value = value;
+        rejected.verdict = if offered == 0 {
+            Verdict::NoSourceShips
+        } else {
+            Verdict::NoTime {
+                best_offer: offered,
+            }
+        };
+        rejected.offers = usable_planets
+            .iter()
+            .map(|p| Offer {
+                planet: p.id,
+                dist: p.dist,
+                usable_ships: p.usable_ships,
+                sent: None,
+            })
+            .collect();
+    }
+
+    Err(rejected)
+}
+
+#[allow(unused)]
+fn print_operations(state: &State, operation: &[Operation]) {
+    for operation in operation {
+        eprintln!("Operation:");
+        for part in &operation.solution {
+            eprintln!(
+                "  {} --{}-> {}",
+                state.inv_planet_map[part.source], part.ships, state.inv_planet_map[part.target]
+            );
+        }
+    }
+}
+
+struct Evaluation {
+    worker: usize,
+    best: Vec<Operation>,
+    /// Why every operation of the best prefix was accepted or dropped
+    explanations: Vec<Explanation>,
+    best_score: f32,
+    /// Length of the prefix of the ordering that gave `best`
+    best_tried: usize,
+    /// Length of the prefix that was tried before the deadline
+    tried: usize,
+    max_len: usize,
+}
+
+/// Worker `w` tries candidate `w` first and the others in heap order,
+/// so worker 0 always covers the plain heap order
+fn ordering(candidates: &[OptionalOperation], w: usize) -> Vec<&OptionalOperation> {
+    candidates
+        .get(w)
+        .into_iter()
+        .chain(
+            candidates
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != w)
+                .map(|(_, c)| c),
+        )
+        .collect()
+}
+
+/// Greedily grows the set of tried operations in the given order, keeping the best solution
+fn evaluate_ordering(
+    worker: usize,
+    ordering: &[&OptionalOperation],
+    state: &State,
+    started: Instant,
+    cancel: &AtomicBool,
+) -> Evaluation {
+    let mut out = Evaluation {
+        worker,
+        best: Vec::new(),
+        explanations: Vec::new(),
+        best_score: f32::MIN,
+        best_tried: 0,
+        tried: 0,
+        max_len: 0,
+    };
+
+    let search_time = Duration::from_millis(state.config().search_ms);
+    for tried in 1..=ordering.len() {
+        if started.elapsed() >= search_time || cancel.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let (o, explanations) = try_oo(ordering[..tried].iter().copied(), state);
+
+        let score = o.iter().map(|x| x.score).sum();
+        out.tried = tried;
+        out.max_len = out.max_len.max(o.len());
+        if score > out.best_score {
+            out.best_score = score;
+            out.best_tried = tried;
+            out.best = o;
+            out.explanations = explanations;
+        }
+    }
+
+    out
+}
+
+fn planned(plan: &Plan, state: &State) -> Explanation {
+    let target = &state.planets()[plan.target];
+    let required_ships =
+        target[plan.arrival].ships + 1 + reinforcements(target, plan.arrival, state);
+    Explanation {
+        target: plan.target,
+        duration: Some(plan.arrival),
+        required_ships,
+        value: planet_value(
+            target,
+            target[plan.arrival].owner,
+            plan.arrival,
+            required_ships,
+            state,
+        ),
+        score: None,
+        offers: plan
+            .sends
+            .iter()
+            .map(|send| Offer {
+                planet: send.source,
+                dist: state.planets()[send.source].distance(target),
+                usable_ships: send.ships,
+                sent: None,
+            })
+            .collect(),
+        verdict: Verdict::Planned,
+    }
+}
+
+/// Feasible operations, best score first, and explanations for the rejected targets
+pub fn candidates(state: &State) -> (Vec<OptionalOperation>, Vec<Explanation>) {
+    let mut b_heap = BinaryHeap::new();
+    let rejected: Vec<_> = state
+        .planets()
+        .iter()
+        .filter_map(
+            |p| match state.plans().iter().find(|x| x.target == p.id()) {
+                Some(plan) => Some(planned(plan, state)),
+                None => {
+                    // A snipe replaces the plain attack when it scores better
+                    let op = match (find_optional_operations(p, state), find_snipe(p, state)) {
+                        (Ok(op), Some(snipe)) if snipe.score() > op.score() => snipe,
+                        (Ok(op), _) => op,
+                        (Err(_), Some(snipe)) => snipe,
+                        (Err(rejected), None) => return Some(rejected),
+                    };
+                    b_heap.push(op);
+                    None
+                }
+            },
+        )
+        .collect();
+
+    (
+        b_heap.into_sorted_vec().into_iter().rev().collect(),
+        rejected,
+    )
+}
+
+/// First future in which `planet` is no longer ours
+fn lost_at(planet: &PlanetStates) -> Option<usize> {
+    planet.futures().position(|x| x.owner != ME)
+}
+
+/// The operation to drop first when simulating the sends of `operations`, together with
+/// this turn's moves, misses a target or loses a planet earlier than we would anyway
+fn first_failure(operations: &[Operation], state: &State) -> Option<(usize, &'static str)> {
+    let mut overlay = Overlay::new(state);
+    for &(source, target, ships) in state.pending_moves() {
+        overlay.send(source, target, ships, 0);
+    }
+    for (i, op) in operations.iter().enumerate() {
+        for part in &op.solution {
+            if !overlay.send(part.source, part.target, part.ships, part.delay) {
+                return Some((i, "its sends land past the longest horizon"));
+            }
+        }
+    }
+
+    if let Some(i) = operations
+        .iter()
+        .position(|op| overlay.planet(op.target)[op.arrival].owner != ME)
+    {
+        return Some((i, "its sends do not capture it"));
+    }
+
+    let touched: Vec<_> = overlay.touched().collect();
+    let weakened: Vec<_> = touched
+        .into_iter()
+        .filter(
+            |&id| match (lost_at(&state.planets()[id]), lost_at(overlay.planet(id))) {
+                (Some(0), _) | (_, None) => false,
+                (None, Some(_)) => true,
+                (Some(before), Some(after)) => after < before,
+            },
+        )
+        .collect();
+
+    // Of the operations sending from a planet we lose, the worst goes
+    operations
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| op.solution.iter().any(|x| weakened.contains(&x.source)))
+        .min_by(|(_, a), (_, b)| a.score.total_cmp(&b.score))
+        .map(|(i, _)| (i, "its sends lose a planet"))
+}
+
+/// Time after the search `verify` may take, the turn deadline leaves room for it
+const VERIFY_TIME: Duration = Duration::from_millis(100);
+
+/// Drops operations until the rest survive `first_failure`, returns their targets.
+/// Out of time or cancelled, the operations not verified yet are dropped too.
+fn verify(
+    operations: &mut Vec<Operation>,
+    state: &State,
+    started: Instant,
+    cancel: &AtomicBool,
+) -> Vec<usize> {
+    let deadline = Duration::from_millis(state.config().search_ms) + VERIFY_TIME;
+    let mut dropped = Vec::new();
+    loop {
+        let failure = if started.elapsed() >= deadline || cancel.load(Ordering::Relaxed) {
+            // Ships stay home rather than leave unchecked
+            operations
+                .len()
+                .checked_sub(1)
+                .map(|i| (i, "there was no time to verify it"))
+        } else {
+            first_failure(operations, state)
+        };
+        let Some((i, reason)) = failure else {
+            break;
+        };
+        let op = operations.remove(i);
+        eprintln!(
+            "Dropping the operation on {}, {}",
+            state.inv_planet_map[op.target], reason
+        );
+        dropped.push(op.target);
+    }
+    dropped
+}
+
+/// Evaluates orderings of `candidates` on every worker thread, returns the best evaluation
+/// with the longest set of operations any worker found
+fn search(
+    candidates: &[OptionalOperation],
+    state: &State,
+    started: Instant,
+    cancel: &AtomicBool,
+) -> Evaluation {
+    let workers = state.config().threads.clamp(1, candidates.len().max(1));
+    let evaluations: Vec<Evaluation> = thread::scope(|s| {
+        let handles: Vec<_> = (0..workers)
+            .map(|w| {
+                s.spawn(move || {
+                    evaluate_ordering(w, &ordering(candidates, w), state, started, cancel)
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect()
+    });
+
+    let max_len = evaluations.iter().map(|e| e.max_len).max().unwrap_or(0);
+    let best = evaluations
+        .into_iter()
+        .max_by(|a, b| a.best_score.total_cmp(&b.best_score))
+        .unwrap();
+    Evaluation { max_len, ..best }
+}
+
+/// The flow network of the operations the search settles on, as Graphviz dot
+pub fn chosen_dot(state: &State) -> String {
+    let (candidates, _) = candidates(state);
+    let best = search(&candidates, state, Instant::now(), &AtomicBool::new(false));
+    let ordering = ordering(&candidates, best.worker);
+    oo_to_dot(ordering[..best.best_tried].iter().copied(), state)
+}
+
+/// Executes the best set of operations found before the deadline or `cancel`.
+/// Returns an explanation for every target that was considered.
+pub fn best_planet(state: &mut State, started: Instant, cancel: &AtomicBool) -> Vec<Explanation> {
+    for risk in snipe_risks(state) {
+        eprintln!(
+            "Enemy can snipe {} after we take it in {} turns ({} ships against {})",
+            state.inv_planet_map[risk.planet], risk.capture, risk.garrison, risk.threat
+        );
+    }
+
+    for p in state.planets() {
+        let weakness = state.weakness(p.id());
+        if weakness >= 0.5 {
+            eprintln!(
+                "{} sent {:.0}% of its garrison away, counter-attacking",
+                state.inv_planet_map[p.id()],
+                weakness * 100.
+            );
+        }
+    }
+
+    let (candidates, mut explanations) = candidates(state);
+
+    let Evaluation {
+        worker,
+        mut best,
+        explanations: tried_explanations,
+        best_score,
+        best_tried,
+        tried,
+        max_len,
+    } = search(&candidates, state, started, cancel);
+
+    let ordering = ordering(&candidates, worker);
+    explanations.extend(tried_explanations);
+    explanations.extend(ordering.iter().enumerate().skip(best_tried).map(|(i, op)| {
+        let verdict = if i < tried {
+            Verdict::NoImprovement
+        } else {
+            Verdict::NotEvaluated
+        };
+        Explanation::candidate(op, verdict)
+    }));
+
+    let dropped = verify(&mut best, state, started, cancel);
+    for x in &mut explanations {
+        if x.verdict == Verdict::Accepted && dropped.contains(&x.target) {
+            x.verdict = Verdict::Unverified;
+        }
+    }
+
+    eprintln!(
+        "Executing {} operations with total score {} (max len {})",
+        best.len(),
+        best_score,
+        max_len
+    );
+
+    for operation in best {
+        state
+            .stats_mut()
+            .operation(operation.target, operation.arrival);
+
+        let mut sends = Vec::new();
+        for part in operation.solution {
+            if part.delay == 0 {
+                state.add_turn(part.source, part.target, part.ships);
+            } else {
+                sends.push(PlannedSend {
+                    source: part.source,
+                    ships: part.ships,
+                    delay: part.delay,
+                });
+            }
+        }
+
+        if !sends.is_empty() {
+            state.add_plan(Plan {
+                target: operation.target,
+                arrival: operation.arrival,
+                sends,
+            });
+        }
+    }
+
+    explanations
+}
+
+/// Plans this turn's moves, they are left in `state` to be flushed in the right protocol.
+/// The search stops early once `cancel` is set.
+pub fn turn(
+    state: &mut State,
+    started: Instant,
+    turn_count: usize,
+    cancel: &AtomicBool,
+) -> Vec<Explanation> {
+    state.execute_plans();
+
+    if turn_count >= OPENING_HORIZON || !opening(state) {
+        best_planet(state, started, cancel)
+    } else {
+        Vec::new()
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|x| x.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+type Planner = fn(&mut State, Instant, usize, &AtomicBool) -> Vec<Explanation>;
+
+/// Runs `turn` on a copy of the state in a separate thread. When it panics or misses
+/// `deadline`, the copy is abandoned, the plans due this turn are executed and
+/// `simple_turn` plans on the real state instead.
+pub fn guarded_turn(
+    state: &mut State,
+    started: Instant,
+    turn_count: usize,
+    deadline: Duration,
+    trace: &mut Trace,
+) {
+    guarded(state, started, turn_count, deadline, trace, turn)
+}
+
+/// A planner that misses the deadline is cancelled, so late threads do not pile up
+fn guarded(
+    state: &mut State,
+    started: Instant,
+    turn_count: usize,
+    deadline: Duration,
+    trace: &mut Trace,
+    planner: Planner,
+) {
+    let (tx, rx) = mpsc::channel();
+    let mut planned = state.clone();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let cancelled = cancel.clone();
+
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let explanations = planner(&mut planned, started, turn_count, &cancelled);
+            (planned, explanations)
+        }));
+        let _ = tx.send(result.map_err(panic_message));
+    });
+
+    let reason = match rx.recv_timeout(deadline.saturating_sub(started.elapsed())) {
+        Ok(Ok((planned, candidates))) => {
+            *state = planned;
+            trace.record(TraceEvent::Explanations {
+                turn: turn_count,
+                candidates,
+            });
+            return;
+        }
+        Ok(Err(msg)) => format!("planner panicked: {}", msg),
+        Err(_) => {
+            cancel.store(true, Ordering::Relaxed);
+            format!("planner missed the {:?} deadline", deadline)
+        }
+    };
+
+    trace.record(TraceEvent::PlannerFailed {
+        turn: turn_count,
+        reason,
+        fallback: "simple_turn",
+    });
+
+    // Sends already promised still leave, unless executing the plans is what failed
+    let mut executed = state.clone();
+    if panic::catch_unwind(AssertUnwindSafe(|| executed.execute_plans())).is_ok() {
+        *state = executed;
+    }
+    simple_turn(state);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        best_planet, candidates, chosen_dot,
+        config::Config,
+        evaluate_ordering,
+        explain::Verdict,
+        find_optional_operations,
+        graphs::{try_oo, Operation, SolutionPart},
+        guarded, guarded_turn, hold_ships,
+        models::{recorded::*, *},
+        ordering, reinforcements, search,
+        trace::Trace,
+        value::planet_value,
+        verify, EnemyResponse,
+    };
+
+    #[test]
+    fn missed_deadline_falls_back() {
+        let mut state = state_at(HEX, 0);
+
+        guarded_turn(
+            &mut state,
+            Instant::now(),
+            0,
+            Duration::ZERO,
+            &mut Trace::default(),
+        );
+
+        let protos = state.planet_id("protos").unwrap();
+        let moves = state.take_moves();
+        assert_eq!(moves.len(), 1);
+        assert_eq!((moves[0].0, moves[0].2), (protos, 5));
+    }
+
+    #[test]
+    fn failing_planners_fall_back() {
+        let state = state_at(HEX, 0);
+        let protos = state.planet_id("protos").unwrap();
+        let fallback = |planner| {
+            let mut state = state.clone();
+            let deadline = Duration::from_millis(200);
+            guarded(
+                &mut state,
+                Instant::now(),
+                0,
+                deadline,
+                &mut Trace::default(),
+                planner,
+            );
+            state.take_moves()
+        };
+
+        let moves = fallback(|_, _, _, _| panic!("planner bug"));
+        assert_eq!((moves[0].0, moves[0].2), (protos, 5));
+
+        // A late planner is told to stop instead of running on in the background
+        static STOPPED: AtomicBool = AtomicBool::new(false);
+        let moves = fallback(|_, _, _, cancel| {
+            while !cancel.load(Ordering::Relaxed) {
+                thread::yield_now();
+            }
+            STOPPED.store(true, Ordering::Relaxed);
+            Vec::new()
+        });
+        assert_eq!((moves[0].0, moves[0].2), (protos, 5));
+
+        let waited = Instant::now();
+        while !STOPPED.load(Ordering::Relaxed) {
+            assert!(waited.elapsed() < Duration::from_secs(5));
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn fallback_keeps_the_plans() {
+        let input = r#"{"planets":[
+            {"ship_count":50,"x":0,"y":0,"owner":1,"name":"home"},
+            {"ship_count":5,"x":3,"y":0,"owner":null,"name":"neutral"},
+            {"ship_count":50,"x":20,"y":0,"owner":2,"name":"enemy"}
+        ],"expeditions":[]}"#;
+        let mut state = State::new(serde_json::from_str(input).unwrap());
+        state.add_plan(Plan {
+            target: 1,
+            arrival: 5,
+            sends: vec![
+                PlannedSend {
+                    source: 0,
+                    ships: 3,
+                    delay: 0,
+                },
+                PlannedSend {
+                    source: 0,
+                    ships: 4,
+                    delay: 2,
+                },
+            ],
+        });
+
+        let deadline = Duration::from_millis(200);
+        let planner: super::Planner = |_, _, _, _| panic!("planner bug");
+        guarded(
+            &mut state,
+            Instant::now(),
+            0,
+            deadline,
+            &mut Trace::default(),
+            planner,
+        );
+
+        // The due send goes out first, simple_turn adds its own move after it
+        assert_eq!(state.pending_moves()[0], (0, 1, 3));
+        assert_eq!(state.plans()[0].sends.len(), 1);
+    }
+
+    #[test]
+    fn keeps_the_best_prefix() {
+        // Every ordering is tried, however slow the build
+        let config = Config {
+            search_ms: 60_000,
+            ..Config::default()
+        };
+        let state = State::with_config(input_at(SPIRAL, 40), config);
+        let (candidates, _) = candidates(&state);
+        assert!(candidates.len() > 2);
+
+        let order = ordering(&candidates, 1);
+        assert_eq!(order[0], &candidates[1]);
+        assert_eq!(order[1], &candidates[0]);
+        assert_eq!(order[2], &candidates[2]);
+
+        let cancel = AtomicBool::new(false);
+        let evaluation = evaluate_ordering(1, &order, &state, Instant::now(), &cancel);
+        let scores: Vec<f32> = (1..=order.len())
+            .map(|n| {
+                try_oo(order[..n].iter().copied(), &state)
+                    .0
+                    .iter()
+                    .map(|x| x.score)
+                    .sum()
+            })
+            .collect();
+        assert_eq!(evaluation.tried, order.len());
+        assert_eq!(evaluation.best_score, scores[evaluation.best_tried - 1]);
+        assert!(scores.iter().all(|&x| x <= evaluation.best_score));
+
+        // Nothing is tried past the search time
+        let config = Config {
+            search_ms: 0,
+            ..state.config().clone()
+        };
+        let state = State::with_config(input_at(SPIRAL, 40), config);
+        let evaluation = evaluate_ordering(1, &order, &state, Instant::now(), &cancel);
+        assert_eq!(evaluation.tried, 0);
+        assert!(evaluation.best.is_empty());
+    }
+
+    #[test]
+    fn explains_every_target_once() {
+        let mut state = state_at(SPIRAL, 40);
+        let explanations = best_planet(&mut state, Instant::now(), &AtomicBool::new(false));
+
+        let mut targets: Vec<_> = explanations.iter().map(|x| x.target).collect();
+        targets.sort();
+        targets.dedup();
+        assert_eq!(targets.len(), explanations.len());
+        assert_eq!(targets.len(), state.planets().len());
+
+        let mut accepted: Vec<_> = explanations
+            .iter()
+            .filter(|x| x.verdict == Verdict::Accepted)
+            .map(|x| x.target)
+            .collect();
+        accepted.sort();
+        let mut attacked: Vec<_> = state.plans().iter().map(|x| x.target).collect();
+        attacked.extend(state.take_moves().into_iter().map(|(_, target, _)| target));
+        attacked.sort();
+        attacked.dedup();
+        assert!(!accepted.is_empty());
+        assert_eq!(accepted, attacked);
+    }
+
+    #[test]
+    fn dot_shows_the_chosen_prefix() {
+        let state = state_at(SPIRAL, 40);
+        let (candidates, _) = candidates(&state);
+        let best = search(&candidates, &state, Instant::now(), &AtomicBool::new(false));
+
+        let dot = chosen_dot(&state);
+        assert!(best.best_tried > 0);
+        assert_eq!(dot.matches("shape=box").count(), best.best_tried);
+    }
+
+    #[test]
+    fn captures_what_it_can_hold() {
+        let state = |enemy: i32| {
+            let input = format!(
+                r#"{{"planets":[
+                    {{"ship_count":60,"x":0,"y":0,"owner":1,"name":"home"}},
+                    {{"ship_count":5,"x":2,"y":0,"owner":null,"name":"near"}},
+                    {{"ship_count":{},"x":8,"y":0,"owner":2,"name":"enemy"}}
+                ],"expeditions":[]}}"#,
+                enemy
+            );
+            State::new(serde_json::from_str(&input).unwrap())
+        };
+
+        // The enemy garrison lands 4 turns after us, half of it is risked, our ship grows too
+        let held = state(20);
+        let op = find_optional_operations(&held.planets()[1], &held).unwrap();
+        assert_eq!(op.required_ships, 6 + 10 - 4);
+        assert_eq!(
+            op.value,
+            planet_value(&held.planets()[1], NEUTRAL, 2, 6, &held)
+        );
+
+        // Home cannot spare enough to hold against this one, the capture is worth less
+        let state = state(100);
+        let op = find_optional_operations(&state.planets()[1], &state).unwrap();
+        assert_eq!(op.required_ships, 6);
+        assert!(op.value < planet_value(&state.planets()[1], NEUTRAL, 2, 6, &state));
+    }
+
+    #[test]
+    fn skips_captures_that_do_not_pay_off() {
+        let input = r#"{"planets":[
+            {"ship_count":60,"x":0,"y":0,"owner":1,"name":"home"},
+            {"ship_count":20,"x":2,"y":0,"owner":null,"name":"near"}
+        ],"expeditions":[]}"#;
+        let state = |game_length| {
+            let config = Config {
+                game_length,
+                ..Config::default()
+            };
+            State::with_config(serde_json::from_str(input).unwrap(), config)
+        };
+
+        let early = state(500);
+        assert!(find_optional_operations(&early.planets()[1], &early).is_ok());
+
+        // 8 turns of production do not make up for 21 ships
+        let late = state(10);
+        let rejected = find_optional_operations(&late.planets()[1], &late).unwrap_err();
+        assert_eq!(rejected.verdict, Verdict::NotWorth);
+        assert_eq!(rejected.value, 0.);
+    }
+
+    #[test]
+    fn neighbours_help_to_hold() {
+        let state = |outpost: &str| {
+            let input = format!(
+                r#"{{"planets":[
+                    {{"ship_count":60,"x":0,"y":0,"owner":1,"name":"home"}},
+                    {{"ship_count":5,"x":2,"y":0,"owner":null,"name":"near"}},
+                    {{"ship_count":30,"x":4,"y":0,"owner":{},"name":"outpost"}},
+                    {{"ship_count":20,"x":8,"y":0,"owner":2,"name":"enemy"}}
+                ],"expeditions":[]}}"#,
+                outpost
+            );
+            State::new(serde_json::from_str(&input).unwrap())
+        };
+
+        // Half the enemy garrison lands 4 turns after us
+        let alone = state("null");
+        assert_eq!(hold_ships(&alone.planets()[1], 2, &alone), 10 - 4);
+        // The outpost keeps as many back against it, and is closer than the enemy
+        let guarded = state("1");
+        assert_eq!(hold_ships(&guarded.planets()[1], 2, &guarded), 0);
+    }
+
+    #[test]
+    fn expects_the_enemy_to_reinforce() {
+        let input = r#"{"planets":[
+            {"ship_count":100,"x":0,"y":0,"owner":1,"name":"home"},
+            {"ship_count":5,"x":5,"y":0,"owner":2,"name":"target"},
+            {"ship_count":20,"x":7,"y":0,"owner":2,"name":"cluster"},
+            {"ship_count":20,"x":30,"y":0,"owner":2,"name":"far"},
+            {"ship_count":5,"x":0,"y":5,"owner":null,"name":"neutral"}
+        ],"expeditions":[]}"#;
+        let state = State::new(serde_json::from_str(input).unwrap());
+
+        // The cluster launches 3 turns in with 23 ships, far never makes it, half is planned for
+        assert_eq!(reinforcements(&state.planets()[1], 5, &state), 7);
+        assert_eq!(reinforcements(&state.planets()[4], 5, &state), 0);
+        // The cluster cannot also take the planet back with those ships
+        let target = &state.planets()[1];
+        let response = EnemyResponse::new(target, 5, &state);
+        assert_eq!(response.hold, hold_ships(target, 5, &state) - 7);
+
+        let op = find_optional_operations(&state.planets()[1], &state).unwrap();
+        assert!(op.required_ships >= 10 + 1 + 7);
+    }
+
+    #[test]
+    fn drops_operations_that_fail_in_simulation() {
+        let state = state_at(HEX, 0);
+        let protos = state.planet_id("protos").unwrap();
+        let duteros = state.planet_id("duteros").unwrap();
+        let extos = state.planet_id("extos").unwrap();
+        let arrival = state.planets()[protos].distance(&state.planets()[duteros]);
+
+        let operation = |target, ships, delay| Operation {
+            score: 1.,
+            target,
+            arrival: arrival + delay,
+            solution: vec![SolutionPart {
+                source: protos,
+                target,
+                ships,
+                delay,
+            }],
+        };
+        // Six ships only tie with the neutral garrison, eight have grown in two turns
+        let operations = || vec![operation(duteros, 6, 0), operation(extos, 8, 2)];
+        let cancel = AtomicBool::new(false);
+        let mut verified = operations();
+        assert_eq!(
+            verify(&mut verified, &state, Instant::now(), &cancel),
+            vec![duteros]
+        );
+        assert_eq!(verified.len(), 1);
+
+        // Without time left nothing goes out unchecked
+        let late = Instant::now() - Duration::from_secs(5);
+        let mut operations = operations();
+        assert_eq!(
+            verify(&mut operations, &state, late, &cancel),
+            vec![extos, duteros]
+        );
+        assert!(operations.is_empty());
+    }
+}
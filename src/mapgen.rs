@@ -0,0 +1,258 @@
+use std::{f32::consts::PI, str::FromStr};
+
+use crate::engine::{Map, Planet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    /// Rings of evenly spaced planets, a single ring for two players is a hexagon
+    Hex,
+    /// One arm per player winding out of a big center planet
+    Spiral,
+    /// Random planets, copied to every player's slice of the map
+    Scatter,
+}
+
+impl FromStr for Family {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(Family::Hex),
+            "spiral" => Ok(Family::Spiral),
+            "scatter" => Ok(Family::Scatter),
+            _ => Err(format!("Unknown map family {:?}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MapOptions {
+    pub family: Family,
+    pub players: usize,
+    /// Rounded down to a multiple of `players`, plus a center planet when that left some over.
+    /// Scatter maps get fewer when the planets do not fit the spread.
+    pub planets: usize,
+    /// Distance from the center to the outermost planets
+    pub spread: f32,
+    pub home_ships: i32,
+    /// Most ships a neutral planet starts with
+    pub neutral_ships: i32,
+    pub seed: u64,
+}
+
+impl Default for MapOptions {
+    fn default() -> Self {
+        Self {
+            family: Family::Hex,
+            players: 2,
+            planets: 6,
+            spread: 6.,
+            home_ships: 6,
+            neutral_ships: 6,
+            seed: 0,
+        }
+    }
+}
+
+/// splitmix64, maps only need to be reproducible from their seed
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn float(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn ships(&mut self, max: i32) -> i32 {
+        1 + (self.next() % max.max(1) as u64) as i32
+    }
+}
+
+fn rotate((x, y): (f32, f32), angle: f32) -> (f32, f32) {
+    let (sin, cos) = angle.sin_cos();
+    (x * cos - y * sin, x * sin + y * cos)
+}
+
+/// Three decimals like the recorded maps, without negative zeros
+fn round((x, y): (f32, f32)) -> (f32, f32) {
+    let round = |v: f32| (v * 1000.).round() / 1000. + 0.;
+    (round(x), round(y))
+}
+
+/// Planets of the first player's slice as location and neutral ships, the home comes first
+fn slice(options: &MapOptions, rng: &mut Rng) -> Vec<((f32, f32), i32)> {
+    let n = options.planets / options.players.max(1);
+    let wedge = 2. * PI / options.players as f32;
+    let spread = options.spread;
+
+    match options.family {
+        Family::Hex => {
+            let rings = n.div_ceil(3);
+            (0..n)
+                .map(|i| {
+                    let (ring, vertex) = (i / 3, i % 3);
+                    let radius = spread * (rings - ring) as f32 / rings as f32;
+                    let angle = wedge * vertex as f32 / 3. + ring as f32 * wedge / 6.;
+                    let loc = rotate((radius, 0.), angle);
+                    (loc, rng.ships(options.neutral_ships))
+                })
+                .collect()
+        }
+        Family::Spiral => (0..n)
+            .map(|i| {
+                let step = (n - i) as f32 / n as f32;
+                let loc = rotate((spread * step, 0.), step * PI / 2.);
+                let ships = (options.neutral_ships as f32 * (1. - step + 1. / n as f32)).ceil();
+                (loc, ships as i32)
+            })
+            .collect(),
+        Family::Scatter => {
+            let mut out: Vec<((f32, f32), i32)> = Vec::new();
+            let min_dist = spread / (options.planets as f32).sqrt();
+            let has_center = !options.planets.is_multiple_of(options.players);
+            let dist = |(ax, ay): (f32, f32), (bx, by): (f32, f32)| (ax - bx).hypot(ay - by);
+
+            // Checking `loc` against every copy of the others, and its own copies, is enough
+            let far_enough = |loc: (f32, f32), out: &[((f32, f32), i32)]| {
+                let copies = |p| (0..options.players).map(move |j| rotate(p, wedge * j as f32));
+                (!has_center || dist(loc, (0., 0.)) >= min_dist)
+                    && copies(loc).skip(1).all(|c| dist(c, loc) >= min_dist)
+                    && out
+                        .iter()
+                        .flat_map(|&(p, _)| copies(p))
+                        .all(|c| dist(c, loc) >= min_dist)
+            };
+
+            for _ in 0..n * 100 {
+                if out.len() == n {
+                    break;
+                }
+                let loc = rotate((spread * rng.float().sqrt(), 0.), wedge * rng.float());
+                if far_enough(loc, &out) {
+                    out.push((loc, rng.ships(options.neutral_ships)));
+                }
+            }
+
+            // The home is the planet farthest out
+            out.sort_by(|((ax, ay), _), ((bx, by), _)| bx.hypot(*by).total_cmp(&ax.hypot(*ay)));
+            out
+        }
+    }
+}
+
+/// Generates a map with rotational symmetry, player `j` gets the home of the `j`th slice.
+/// Fails when not even the homes fit.
+pub fn generate(options: &MapOptions) -> Result<Map, String> {
+    if options.players == 0 || options.planets < options.players {
+        return Err("Every player needs a planet".to_string());
+    }
+
+    let mut rng = Rng(options.seed);
+    let base = slice(options, &mut rng);
+    if base.is_empty() {
+        return Err(format!(
+            "No planets fit a spread of {} for {} players",
+            options.spread, options.players
+        ));
+    }
+    let wedge = 2. * PI / options.players as f32;
+
+    let mut planets = Vec::new();
+    if !options.planets.is_multiple_of(options.players) {
+        let name = match options.family {
+            Family::Spiral => "Galactic center".to_string(),
+            _ => "center".to_string(),
+        };
+        planets.push(Planet {
+            ship_count: options.neutral_ships * 2,
+            x: 0.,
+            y: 0.,
+            owner: None,
+            name,
+            growth: 1,
+        });
+    }
+
+    for j in 0..options.players {
+        for (i, &(loc, ships)) in base.iter().enumerate() {
+            let (x, y) = round(rotate(loc, wedge * j as f32));
+            let home = i == 0;
+            planets.push(Planet {
+                ship_count: if home { options.home_ships } else { ships },
+                x,
+                y,
+                owner: home.then_some(j + 1),
+                name: format!("{}-{}", j + 1, i + 1),
+                growth: 1,
+            });
+        }
+    }
+
+    Ok(Map {
+        planets,
+        expeditions: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, Family, MapOptions};
+    use crate::models::{State, SymmetryKind};
+
+    #[test]
+    fn generated_maps_are_symmetric() {
+        for family in [Family::Hex, Family::Spiral, Family::Scatter] {
+            for players in [2, 3, 4] {
+                let options = MapOptions {
+                    family,
+                    players,
+                    planets: 6 * players + 1,
+                    spread: 20.,
+                    seed: 7,
+                    ..MapOptions::default()
+                };
+                let map = generate(&options).unwrap();
+                assert_eq!(map.planets.len(), options.planets, "{:?}", family);
+
+                let line = serde_json::to_string(&map).unwrap();
+                let state = State::new(serde_json::from_str(&line).unwrap());
+                let symmetry = state.symmetry().expect("map is symmetric");
+                // Four players also have the two player symmetry, which is found first
+                assert!(
+                    matches!(symmetry.kind, SymmetryKind::Rotation { order } if players % order == 0),
+                    "{:?} for {} players has {:?}",
+                    family,
+                    players,
+                    symmetry.kind
+                );
+                let again = generate(&options).unwrap();
+                assert_eq!(line, serde_json::to_string(&again).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_maps_without_homes() {
+        let options = MapOptions {
+            family: Family::Scatter,
+            players: 50,
+            planets: 51,
+            ..MapOptions::default()
+        };
+        assert!(generate(&options).is_err());
+
+        let options = MapOptions {
+            players: 3,
+            planets: 2,
+            ..MapOptions::default()
+        };
+        assert!(generate(&options).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use std::io::{self, BufRead, Lines};
+
+use super::{ExpeditionInput, Input, PlanetInput, State};
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn fields<const N: usize>(line: &str) -> io::Result<[&str; N]> {
+    let parts: Vec<_> = line.split_whitespace().skip(1).collect();
+    parts
+        .try_into()
+        .map_err(|_| invalid(format!("Expected {} fields in {:?}", N, line)))
+}
+
+fn num<T: std::str::FromStr>(field: &str) -> io::Result<T> {
+    field
+        .parse()
+        .map_err(|_| invalid(format!("Invalid number {:?}", field)))
+}
+
+/// Reads the line based protocol of the original Planet Wars:
+///
+/// ```text
+/// P <x> <y> <owner> <ships> <growth>
+/// F <owner> <ships> <source> <destination> <total turns> <remaining turns>
+/// go
+/// ```
+///
+/// Planets are named after their index. Fleets carry no id, so new fleets get one here,
+/// a fleet is new when it travelled a single turn.
+pub struct ClassicReader<B> {
+    lines: Lines<B>,
+    next_id: u64,
+    first: bool,
+}
+
+impl<B: BufRead> ClassicReader<B> {
+    pub fn new(input: B) -> Self {
+        Self {
+            lines: input.lines(),
+            next_id: 0,
+            first: true,
+        }
+    }
+}
+
+impl<B> ClassicReader<B> {
+    pub fn parse_turn<'a>(
+        &mut self,
+        lines: impl IntoIterator<Item = &'a str>,
+    ) -> io::Result<Input> {
+        let mut planets = Vec::new();
+        let mut expeditions = Vec::new();
+
+        for line in lines {
+            let line = line.split('#').next().unwrap().trim();
+            match line.chars().next() {
+                Some('P') => {
+                    let [x, y, owner, ships, growth] = fields(line)?;
+                    let owner: usize = num(owner)?;
+                    planets.push(PlanetInput {
+                        ship_count: num(ships)?,
+                        x: num(x)?,
+                        y: num(y)?,
+                        owner: (owner != 0).then_some(owner),
+                        name: planets.len().to_string(),
+                        growth: num(growth)?,
+                    });
+                }
+                Some('F') => {
+                    let [owner, ships, source, destination, total, remaining] = fields(line)?;
+                    let total: usize = num(total)?;
+                    let remaining: usize = num(remaining)?;
+
+                    let id = if self.first || total == remaining + 1 {
+                        self.next_id += 1;
+                        self.next_id - 1
+                    } else {
+                        0
+                    };
+
+                    expeditions.push(ExpeditionInput {
+                        id,
+                        ship_count: num(ships)?,
+                        origin: source.to_string(),
+                        destination: destination.to_string(),
+                        owner: num(owner)?,
+                        turns_remaining: remaining,
+                    });
+                }
+                None => {}
+                _ => return Err(invalid(format!("Unexpected line {:?}", line))),
+            }
+        }
+
+        self.first = false;
+        // Already handled fleets are skipped on their id, keep the new ones last
+        expeditions.sort_by_key(|e| e.id);
+
+        Ok(Input {
+            planets,
+            expeditions,
+        })
+    }
+}
+
+impl<B: BufRead> Iterator for ClassicReader<B> {
+    type Item = io::Result<Input>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut turn = Vec::new();
+        loop {
+            match self.lines.next() {
+                Some(Ok(line)) if line.trim() == "go" => break,
+                Some(Ok(line)) => turn.push(line),
+                Some(Err(e)) => return Some(Err(e)),
+                None if turn.is_empty() => return None,
+                None => break,
+            }
+        }
+
+        Some(self.parse_turn(turn.iter().map(String::as_str)))
+    }
+}
+
+impl State {
+    /// Same as `flush`, formatted as `<source> <destination> <ships>` lines ending with `go`
+    pub fn flush_classic(&mut self) -> String {
+        let mut out = String::new();
+        for (source, target, ships) in self.take_moves() {
+            out += &format!(
+                "{} {} {}\n",
+                self.inv_planet_map[source], self.inv_planet_map[target], ships
+            );
+        }
+        out += "go";
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClassicReader;
+    use crate::models::State;
+
+    const TURN_0: &str = "P 0 0 0 10 5
+P 3 0 1 20 2
+P 0 4 2 20 2
+go
+";
+
+    #[test]
+    fn reads_planets_and_new_fleets() {
+        let input = format!(
+            "{}P 0 0 0 10 5\nP 3 0 1 14 2\nP 0 4 2 20 2\nF 1 8 1 2 5 4\ngo\n",
+            TURN_0
+        );
+        let mut reader = ClassicReader::new(input.as_bytes());
+
+        let mut state = State::new(reader.next().unwrap().unwrap());
+        assert_eq!(state.planets()[1][1].ships, 22);
+
+        state.turn(reader.next().unwrap().unwrap());
+        let target = &state.planets()[2];
+        assert_eq!((target[3].owner, target[3].ships), (2, 26));
+        assert_eq!((target[4].owner, target[4].ships), (2, 28 - 8));
+        assert!(reader.next().is_none());
+
+        state.add_turn(1, 0, 11);
+        assert_eq!(state.flush_classic(), "1 0 11\ngo");
+    }
+}
@@ -3,11 +3,27 @@ use std::{cmp::Ordering, collections::VecDeque};
 use serde::{Deserialize, Serialize};
 use vecs::Vec2;
 
+mod classic;
+mod overlay;
+mod plan;
 mod planet_states;
+mod rules;
 mod state;
+mod symmetry;
+mod threat;
 
+pub use classic::*;
+pub use overlay::*;
+pub use plan::*;
 pub use planet_states::*;
+pub use rules::*;
 pub use state::*;
+pub use symmetry::*;
+pub use threat::*;
+
+fn default_growth() -> i32 {
+    1
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PlanetInput {
@@ -16,13 +32,18 @@ pub struct PlanetInput {
     y: f32,
     owner: Option<usize>,
     name: String,
+    /// Not part of the json protocol, where every planet grows by one
+    #[serde(default = "default_growth")]
+    growth: i32,
 }
 
+#[allow(unused)]
 #[derive(Clone, Copy)]
 pub struct PlanetOrderer {
     from: Vec2<f32>,
 }
 
+#[allow(unused)]
 impl PlanetOrderer {
     pub fn vec2(from: Vec2<f32>) -> Self {
         Self { from }
@@ -68,12 +89,34 @@ impl FnMut<(&Planet, &Planet)> for PlanetOrderer {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) fn coords(v: Vec2<f32>) -> (f32, f32) {
+    (v.dot(Vec2::new(1., 0.)), v.dot(Vec2::new(0., 1.)))
+}
+
+/// `Vec2` has no serde support, store it as an `(x, y)` tuple
+mod loc_serde {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use vecs::Vec2;
+
+    pub fn serialize<S: Serializer>(loc: &Vec2<f32>, serializer: S) -> Result<S::Ok, S::Error> {
+        super::coords(*loc).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec2<f32>, D::Error> {
+        let (x, y) = <(f32, f32)>::deserialize(deserializer)?;
+        Ok(Vec2::new(x, y))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Planet {
     pub id: usize,
     pub ships: i32,
     pub owner: Owner,
+    #[serde(with = "loc_serde")]
     pub loc: Vec2<f32>,
+    /// Ships produced every turn while not neutral
+    pub growth: i32,
 }
 
 impl Planet {
@@ -98,7 +141,8 @@ pub struct ExpeditionInput {
     turns_remaining: usize,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Expedition {
     pub id: u64,
     pub ships: i32,
@@ -171,3 +215,21 @@ mod tests {
         assert_eq!(sorter, vec![p0, p1, p2, p3]);
     }
 }
+
+/// Recorded games for the tests, one input per turn
+#[cfg(test)]
+pub(crate) mod recorded {
+    use super::{Input, State};
+
+    pub const HEX: &str = include_str!("../../tests/success_hex.txt");
+    pub const SPIRAL: &str = include_str!("../../tests/success_spiral.txt");
+
+    pub fn input_at(game: &str, turn: usize) -> Input {
+        serde_json::from_str(game.lines().nth(turn).unwrap()).unwrap()
+    }
+
+    /// A state started from the input of `turn`, like the bot would
+    pub fn state_at(game: &str, turn: usize) -> State {
+        State::new(input_at(game, turn))
+    }
+}
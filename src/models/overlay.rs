@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+
+use super::*;
+
+/// Hypothetical sends on top of a `State`. Only the planets they touch are copied, and
+/// their futures are recomputed when asked for. Dropping it leaves the state as it was.
+/// The threat map is not updated, our own sends do not move enemy garrisons.
+pub struct Overlay<'a> {
+    state: &'a State,
+    planets: BTreeMap<usize, PlanetStates>,
+}
+
+impl<'a> Overlay<'a> {
+    pub fn new(state: &'a State) -> Self {
+        Self {
+            state,
+            planets: BTreeMap::new(),
+        }
+    }
+
+    pub fn state(&self) -> &'a State {
+        self.state
+    }
+
+    fn copy(&mut self, id: usize) -> &mut PlanetStates {
+        let state = self.state;
+        self.planets
+            .entry(id)
+            .or_insert_with(|| state.planets()[id].clone())
+    }
+
+    /// Sends `ships` from `source` to `target` in `delay` turns, the copies look as far
+    /// ahead as it takes. False when the landing is past `MAX_HORIZON` and was not recorded.
+    pub fn send(&mut self, source: usize, target: usize, ships: i32, delay: usize) -> bool {
+        let planets = self.state.planets();
+        let remaining = delay + planets[source].distance(&planets[target]) - 1;
+        if remaining >= MAX_HORIZON {
+            return false;
+        }
+
+        // Earlier sends may change who owns the source by then
+        let copy = self.copy(source);
+        copy.extend_horizon(delay + 1);
+        copy.recalculate();
+        let owner = copy[delay].owner;
+        if delay == 0 {
+            copy.dispatch(ships);
+        } else {
+            copy.departure(delay, owner, ships);
+        }
+        self.copy(target).incoming_exp(&Expedition {
+            id: u64::MAX,
+            ships,
+            remaining,
+            owner,
+            origin: source,
+            destination: target,
+        });
+        true
+    }
+
+    /// The futures of planet `id` with every send so far
+    pub fn planet(&mut self, id: usize) -> &PlanetStates {
+        match self.planets.get_mut(&id) {
+            Some(planet) => {
+                planet.recalculate();
+                planet
+            }
+            None => &self.state.planets()[id],
+        }
+    }
+
+    /// Planets some send leaves from or lands on
+    pub fn touched(&self) -> impl Iterator<Item = usize> + '_ {
+        self.planets.keys().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Overlay;
+    use crate::{
+        config::Config,
+        models::{recorded::*, *},
+    };
+
+    #[test]
+    fn sends_only_change_the_overlay() {
+        let state = state_at(HEX, 0);
+        let protos = state.planet_id("protos").unwrap();
+        let duteros = state.planet_id("duteros").unwrap();
+        let extos = state.planet_id("extos").unwrap();
+        let dist = state.planets()[protos].distance(&state.planets()[duteros]);
+
+        let mut overlay = Overlay::new(&state);
+        assert!(overlay.send(protos, duteros, 6, 0));
+        // Protos grows 2 more ships by then, they land together with the first fleet
+        assert!(overlay.send(protos, duteros, 2, 2));
+        assert!(!overlay.send(protos, duteros, 1, MAX_HORIZON));
+
+        let target = overlay.planet(duteros);
+        assert_eq!(target[dist].owner, NEUTRAL);
+        assert_eq!((target[dist + 2].owner, target[dist + 2].ships), (ME, 2));
+
+        let source = overlay.planet(protos);
+        assert_eq!(source[1].ships, 1);
+        assert_eq!(source[3].ships, 1);
+        assert_eq!(overlay.touched().collect::<Vec<_>>(), vec![protos, duteros]);
+
+        overlay.planet(extos);
+        assert_eq!(state.planets()[duteros][dist + 2].owner, NEUTRAL);
+        assert_eq!(state.planets()[protos][1].ships, 7);
+    }
+
+    #[test]
+    fn sends_past_a_short_horizon() {
+        let config = Config {
+            horizon: Some(4),
+            ..Config::default()
+        };
+        let state = State::with_config(input_at(HEX, 0), config);
+        let protos = state.planet_id("protos").unwrap();
+        let duteros = state.planet_id("duteros").unwrap();
+        let dist = state.planets()[protos].distance(&state.planets()[duteros]);
+
+        let mut overlay = Overlay::new(&state);
+        assert!(overlay.send(protos, duteros, 6, 6));
+
+        let source = overlay.planet(protos);
+        assert_eq!(source.horizon(), 7);
+        assert_eq!(source[7].ships, state.planets()[protos][4].ships + 3 - 6);
+        let target = overlay.planet(duteros);
+        assert_eq!(target.horizon(), 6 + dist);
+    }
+}
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+/// Ships a plan sends from `source` once `delay` runs out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedSend {
+    pub source: usize,
+    pub ships: i32,
+    pub delay: usize,
+}
+
+/// An accepted operation whose contributions leave over several turns,
+/// all of them landing on `target` in `arrival` turns
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    pub target: usize,
+    pub arrival: usize,
+    pub sends: Vec<PlannedSend>,
+}
+
+impl Plan {
+    pub fn pending(&self) -> i32 {
+        self.sends.iter().map(|x| x.ships).sum()
+    }
+
+    /// A missed send stays due, late ships are better than none
+    pub fn tick(&mut self) {
+        self.arrival = self.arrival.saturating_sub(1);
+        for send in &mut self.sends {
+            send.delay = send.delay.saturating_sub(1);
+        }
+    }
+}
@@ -3,16 +3,14 @@ use std::ops::Index;
 use super::*;
 use crate::models::Planet;
 
-#[derive(Clone, Copy, Default, Debug)]
-struct ExpEvent {
-    ships: i32,
-}
+/// Turns predicted ahead are bounded, a bogus coordinate or fleet should not allocate the world
+pub const MAX_HORIZON: usize = 1000;
+/// Most ships a planet, fleet or growth is taken to hold. Far beyond real games, and sums
+/// over thousands of planets still fit an `i32`.
+pub const MAX_SHIPS: i32 = 1 << 20;
 
-impl ExpEvent {
-    fn reset(&mut self) {
-        self.ships = 0;
-    }
-}
+/// Ships landing in one turn per owner, most turns have none and allocate nothing
+type Arrivals = Vec<(Owner, i32)>;
 
 #[derive(Clone, Copy, Default, Debug)]
 struct PlanetState {
@@ -21,78 +19,71 @@ struct PlanetState {
 }
 
 impl PlanetState {
-    fn next(&self) -> Self {
+    fn next(&self, growth: i32) -> Self {
         if self.owner == NEUTRAL {
             *self
         } else {
             Self {
                 owner: self.owner,
-                ships: self.ships + 1,
+                ships: self.ships.saturating_add(growth).min(MAX_SHIPS),
             }
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlanetStates {
-    changed: bool,
+    /// Earliest future that is out of date, the ones before it are still right
+    #[serde(default)]
+    dirty: Option<usize>,
     pub planet: Planet,
-    states: VecDeque<Vec<ExpEvent>>,
+    arrivals: VecDeque<Arrivals>,
     future: VecDeque<Planet>,
+    #[serde(default)]
+    rules: GameRules,
 }
 
-fn new_state(player: usize) -> Vec<ExpEvent> {
-    let mut events = Vec::with_capacity(player);
-    events.resize(player, ExpEvent::default());
-    events
-}
-
-fn execute_combat(current: PlanetState, exp_events: &Vec<ExpEvent>) -> PlanetState {
-    let mut bigest = (NEUTRAL, 0);
-    let mut second = (NEUTRAL, 0);
-
-    for (i, x) in exp_events.iter().enumerate() {
-        let count = if i == current.owner {
-            current.ships + x.ships
-        } else {
-            x.ships
-        };
+/// `forces` is scratch space, reused between turns
+fn execute_combat(
+    rules: &GameRules,
+    current: PlanetState,
+    arrivals: &[(Owner, i32)],
+    forces: &mut Vec<i32>,
+) -> PlanetState {
+    if arrivals.is_empty() {
+        return current;
+    }
 
-        if count > bigest.1 {
-            second = bigest;
-            bigest = (i, count);
-        } else if count > second.1 {
-            second = (i, count);
-        }
+    forces.clear();
+    let players = arrivals.iter().map(|x| x.0).max().unwrap_or_default();
+    forces.resize(players.max(current.owner) + 1, 0);
+    for &(owner, ships) in arrivals {
+        forces[owner] = forces[owner].saturating_add(ships);
     }
+    forces[current.owner] = forces[current.owner].saturating_add(current.ships);
 
-    if bigest.1 == second.1 {
-        PlanetState {
-            ships: 0,
-            owner: NEUTRAL,
-        }
-    } else {
-        PlanetState {
-            ships: bigest.1 - second.1,
-            owner: bigest.0,
-        }
+    let (owner, ships) = rules.combat.resolve(current.owner, forces);
+    PlanetState {
+        owner,
+        ships: ships.min(MAX_SHIPS),
     }
 }
 
 #[allow(unused)]
 impl PlanetStates {
-    pub fn new(planet: Planet, players: usize, max_size: usize) -> Self {
+    pub fn new(planet: Planet, max_size: usize, rules: GameRules) -> Self {
         let mut out = Self {
-            changed: false,
+            dirty: Some(1),
             planet,
-            states: VecDeque::with_capacity(max_size),
+            arrivals: VecDeque::with_capacity(max_size),
             future: VecDeque::with_capacity(max_size + 1),
+            rules,
         };
 
-        out.states.resize(max_size, new_state(players + 1));
+        out.arrivals.resize(max_size, Vec::new());
         out.future.resize(max_size + 1, out.planet);
 
-        out.calculate_states(planet).unwrap();
+        out.calculate_states(planet);
 
         out
     }
@@ -102,84 +93,141 @@ impl PlanetStates {
     }
 
     pub fn distance(&self, other: &Self) -> usize {
-        (*self.planet.loc() - *other.planet.loc()).length().ceil() as usize
+        self.rules
+            .travel_time((*self.planet.loc() - *other.planet.loc()).length())
+    }
+
+    /// Turns ahead expeditions can be tracked
+    pub fn horizon(&self) -> usize {
+        self.arrivals.len()
     }
 
     pub fn futures(&self) -> impl Iterator<Item = &Planet> {
         self.future.iter()
     }
 
+    /// Ships of `owner` that land in the combat of the coming `turn`
+    pub fn arriving(&self, owner: Owner) -> i32 {
+        self.arrivals.front().map_or(0, |arrivals| {
+            arrivals.iter().filter(|x| x.0 == owner).map(|x| x.1).sum()
+        })
+    }
+
+    /// Makes room for arrivals up to `horizon` turns ahead, at most `MAX_HORIZON`
+    pub fn extend_horizon(&mut self, horizon: usize) {
+        let old = self.horizon();
+        let horizon = horizon.min(MAX_HORIZON);
+        if horizon <= old {
+            return;
+        }
+
+        self.arrivals.resize(horizon, Vec::new());
+        self.future.resize(horizon + 1, self.planet);
+        self.touch(old + 1);
+    }
+
+    /// Marks the futures from `from` on as out of date, the current planet is never
+    fn touch(&mut self, from: usize) {
+        let from = from.max(1);
+        self.dirty = Some(self.dirty.map_or(from, |d| d.min(from)));
+    }
+
     pub fn incoming_exp(&mut self, expedition: &Expedition) {
         assert_eq!(expedition.destination, self.planet.id);
 
-        self.states[expedition.remaining][expedition.owner].ships += expedition.ships;
-        self.changed = true;
+        self.extend_horizon(expedition.remaining + 1);
+        let Some(arrivals) = self.arrivals.get_mut(expedition.remaining) else {
+            eprintln!(
+                "Ignoring expedition {} past the longest horizon",
+                expedition.id
+            );
+            return;
+        };
+        match arrivals.iter_mut().find(|x| x.0 == expedition.owner) {
+            Some(x) => x.1 = x.1.saturating_add(expedition.ships),
+            None => arrivals.push((expedition.owner, expedition.ships)),
+        }
+        self.touch(expedition.remaining + 1);
+    }
+
+    /// Ships of `owner` leaving in `delay` turns, they are gone from the garrison
+    /// that fights the turn after, like a dispatch made then
+    pub fn departure(&mut self, delay: usize, owner: Owner, ships: i32) {
+        self.arrivals[delay].push((owner, -ships));
+        self.touch(delay + 1);
     }
 
     pub fn dispatch(&mut self, ship_count: i32) {
         self.planet.ships -= ship_count;
-        self.changed = true;
+        self.touch(1);
     }
 
     pub fn flush(&mut self, planet: Planet) {
-        if self.changed {
-            self.changed = false;
-            self.calculate_states(planet).unwrap();
-        }
+        self.calculate_states(planet);
+    }
+
+    /// Recalculates the futures from the current planet, without new observations
+    pub fn recalculate(&mut self) {
+        self.flush(self.planet);
     }
 
     pub fn turn(&mut self) {
-        if self.planet.owner != NEUTRAL {
-            self.planet.ships += 1;
-        }
+        self.planet.ships += self.rules.growth_of(&self.planet);
 
-        self.states.rotate_left(1);
-        if let Some(st) = self.states.back_mut() {
-            let mut current = PlanetState {
+        if let Some(mut arrivals) = self.arrivals.pop_front() {
+            let current = PlanetState {
                 owner: self.planet.owner,
                 ships: self.planet.ships,
             };
-            current = execute_combat(current, st);
+            let current = execute_combat(&self.rules, current, &arrivals, &mut Vec::new());
             self.planet.owner = current.owner;
             self.planet.ships = current.ships;
 
-            st.iter_mut().for_each(|y| y.reset());
+            arrivals.clear();
+            self.arrivals.push_back(arrivals);
         }
 
-        self.changed = true;
+        // Every future moves one closer, only the new last one is unknown
+        self.future.pop_front();
+        self.future.push_back(self.planet);
+        self.dirty = self.dirty.map(|d| (d - 1).max(1));
+        self.touch(self.horizon());
     }
 
-    fn calculate_states(&mut self, planet: Planet) -> Option<()> {
+    /// Brings the futures up to date, starting from the earliest one that changed
+    fn calculate_states(&mut self, planet: Planet) {
         if planet.owner != self.planet.owner || planet.ships != self.planet.ships {
             eprintln!("Got something looking like a zero turn move");
+            self.planet.ships = planet.ships;
+            self.planet.owner = planet.owner;
+            self.touch(1);
         }
-        self.planet.ships = planet.ships;
-        self.planet.owner = planet.owner;
-
-        let mut current = PlanetState {
-            owner: planet.owner,
-            ships: planet.ships,
-        };
 
         // First state is self
         self.future[0] = self.planet;
 
-        for (state, future) in self.states.iter().zip(self.future.iter_mut().skip(1)) {
+        let Some(from) = self.dirty.take() else {
+            return;
+        };
+        let mut current = PlanetState {
+            owner: self.future[from - 1].owner,
+            ships: self.future[from - 1].ships,
+        };
+
+        let mut forces = Vec::new();
+        for turn in from..self.future.len() {
             // construction
-            current = current.next();
+            current = current.next(self.planet.growth);
 
-            current = execute_combat(current, state);
+            current = execute_combat(&self.rules, current, &self.arrivals[turn - 1], &mut forces);
             // Arrival (owner, count)
 
-            *future = Planet {
+            self.future[turn] = Planet {
                 ships: current.ships,
                 owner: current.owner,
-                id: self.planet.id,
-                loc: self.planet.loc,
+                ..self.planet
             };
         }
-
-        Some(())
     }
 
     pub fn iter_from(&self, start: usize) -> impl Iterator<Item = &Planet> {
@@ -204,6 +252,7 @@ mod tests {
             ships,
             owner,
             loc: Vec2::new(0., 0.),
+            growth: 1,
         }
     }
 
@@ -223,7 +272,7 @@ mod tests {
         let p0 = |i| p(i, 0);
         let p1 = |i| p(i, 1);
 
-        let mut player = PlanetStates::new(p1(0), 2, 5);
+        let mut player = PlanetStates::new(p1(0), 5, GameRules::default());
         player.flush(p1(0));
         assert_eq!(
             player.future,
@@ -237,7 +286,7 @@ mod tests {
             vec![p1(1), p1(2), p1(3), p1(4), p1(5), p1(6)]
         );
 
-        let mut neutral = PlanetStates::new(p0(0), 2, 5);
+        let mut neutral = PlanetStates::new(p0(0), 5, GameRules::default());
         neutral.flush(p0(0));
         assert_eq!(
             neutral.future,
@@ -249,7 +298,7 @@ mod tests {
     fn test_with_ships_one() {
         let p1 = |i| p(i, 1);
 
-        let mut ps = PlanetStates::new(p1(0), 2, 5);
+        let mut ps = PlanetStates::new(p1(0), 5, GameRules::default());
         let exp = e(2, 2, 2);
 
         ps.incoming_exp(&exp);
@@ -267,7 +316,7 @@ mod tests {
         let p1 = |i| p(i, 1);
         let p2 = |i| p(i, 2);
 
-        let mut ps = PlanetStates::new(p1(0), 2, 5);
+        let mut ps = PlanetStates::new(p1(0), 5, GameRules::default());
         let exp = e(5, 2, 2);
 
         ps.incoming_exp(&exp);
@@ -276,11 +325,29 @@ mod tests {
         assert_eq!(ps.future, vec![p1(0), p1(1), p1(2), p2(2), p2(3), p2(4)]);
     }
 
+    #[test]
+    fn test_with_growth() {
+        let p1 = |ships| Planet {
+            growth: 3,
+            ..p(ships, 1)
+        };
+
+        let mut ps = PlanetStates::new(p1(0), 3, GameRules::default());
+        let exp = e(5, 1, 2);
+        ps.incoming_exp(&exp);
+        ps.flush(p1(0));
+        assert_eq!(ps.future, vec![p1(0), p1(3), p1(1), p1(4)]);
+
+        ps.turn();
+        ps.flush(p1(3));
+        assert_eq!(ps.future, vec![p1(3), p1(1), p1(4), p1(7)]);
+    }
+
     #[test]
     fn test_with_dispatch() {
         let p1 = |i| p(i, 1);
 
-        let mut ps = PlanetStates::new(p1(2), 2, 5);
+        let mut ps = PlanetStates::new(p1(2), 5, GameRules::default());
         ps.dispatch(2);
         ps.flush(p1(0));
 
@@ -290,4 +357,30 @@ mod tests {
         ps.flush(p1(1));
         assert_eq!(ps.future, vec![p1(1), p1(2), p1(3), p1(4), p1(5), p1(6)]);
     }
+
+    #[test]
+    fn incremental_matches_full() {
+        let mut ps = PlanetStates::new(p(3, 1), 8, GameRules::default());
+        let fleets = [
+            (5, 3, 2),
+            (2, 6, 1),
+            (7, 1, 2),
+            (4, 7, 2),
+            (9, 2, 1),
+            (1, 5, 2),
+        ];
+
+        for (i, (ships, remaining, owner)) in fleets.into_iter().enumerate() {
+            ps.incoming_exp(&e(ships, remaining, owner));
+            if i % 2 == 1 {
+                ps.turn();
+            }
+            ps.recalculate();
+
+            let mut full = ps.clone();
+            full.touch(1);
+            full.recalculate();
+            assert_eq!(ps.future, full.future, "after fleet {}", i);
+        }
+    }
 }
@@ -0,0 +1,163 @@
+use std::str::FromStr;
+
+use super::*;
+
+/// How distances turn into travel times
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    #[default]
+    Ceil,
+    Round,
+    Floor,
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ceil" => Ok(Rounding::Ceil),
+            "round" => Ok(Rounding::Round),
+            "floor" => Ok(Rounding::Floor),
+            _ => Err(format!("Unknown rounding {:?}", s)),
+        }
+    }
+}
+
+/// Resolves the fight over a planet when fleets land on it
+pub trait Combat {
+    /// New owner and garrison of a planet held by `owner`, where `forces[o]` ships of
+    /// every owner `o` fight, the garrison included
+    fn resolve(&self, owner: Owner, forces: &[i32]) -> (Owner, i32);
+}
+
+/// The two biggest forces, sorted
+fn biggest(forces: &[i32]) -> ((Owner, i32), (Owner, i32)) {
+    let mut biggest = (NEUTRAL, 0);
+    let mut second = (NEUTRAL, 0);
+    for (i, &count) in forces.iter().enumerate() {
+        if count > biggest.1 {
+            second = biggest;
+            biggest = (i, count);
+        } else if count > second.1 {
+            second = (i, count);
+        }
+    }
+    (biggest, second)
+}
+
+/// The biggest force wins with what the second biggest leaves of it, a tie leaves no one
+pub struct Largest;
+
+impl Combat for Largest {
+    fn resolve(&self, _: Owner, forces: &[i32]) -> (Owner, i32) {
+        let (biggest, second) = biggest(forces);
+        if biggest.1 == second.1 {
+            (NEUTRAL, 0)
+        } else {
+            (biggest.0, biggest.1 - second.1)
+        }
+    }
+}
+
+/// Neutral planets fight with `ships` extra defenders that do not stay after the fight
+pub struct NeutralBonus(pub i32);
+
+impl Combat for NeutralBonus {
+    fn resolve(&self, owner: Owner, forces: &[i32]) -> (Owner, i32) {
+        if owner != NEUTRAL {
+            return Largest.resolve(owner, forces);
+        }
+
+        let garrison = forces[NEUTRAL];
+        let mut forces = forces.to_vec();
+        forces[NEUTRAL] = garrison.saturating_add(self.0);
+        match Largest.resolve(owner, &forces) {
+            (NEUTRAL, ships) => (NEUTRAL, ships.min(garrison)),
+            won => won,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CombatRule {
+    #[default]
+    Largest,
+    NeutralBonus {
+        ships: i32,
+    },
+}
+
+impl Combat for CombatRule {
+    fn resolve(&self, owner: Owner, forces: &[i32]) -> (Owner, i32) {
+        match *self {
+            CombatRule::Largest => Largest.resolve(owner, forces),
+            CombatRule::NeutralBonus { ships } => NeutralBonus(ships).resolve(owner, forces),
+        }
+    }
+}
+
+/// The rules of the server being played, predictions are only exact when they match
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameRules {
+    pub travel: Rounding,
+    /// Ships every owned planet grows by, `None` keeps the growth of each planet
+    pub growth: Option<i32>,
+    pub combat: CombatRule,
+}
+
+impl GameRules {
+    /// Turns a fleet needs for `distance`, at least one
+    pub fn travel_time(&self, distance: f32) -> usize {
+        let turns = match self.travel {
+            Rounding::Ceil => distance.ceil(),
+            Rounding::Round => distance.round(),
+            Rounding::Floor => distance.floor(),
+        };
+        (turns as usize).max(1)
+    }
+
+    /// Growth of a planet that comes with `growth`
+    pub fn planet_growth(&self, growth: i32) -> i32 {
+        self.growth.unwrap_or(growth)
+    }
+
+    /// Ships `planet` grows by this turn, neutrals do not grow
+    pub fn growth_of(&self, planet: &Planet) -> i32 {
+        if planet.owner == NEUTRAL {
+            0
+        } else {
+            planet.growth
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Combat, CombatRule, GameRules, Rounding, NEUTRAL};
+
+    #[test]
+    fn rule_variants() {
+        let rules = |travel| GameRules {
+            travel,
+            ..GameRules::default()
+        };
+        assert_eq!(rules(Rounding::Ceil).travel_time(2.2), 3);
+        assert_eq!(rules(Rounding::Round).travel_time(2.2), 2);
+        assert_eq!(rules(Rounding::Floor).travel_time(0.5), 1);
+
+        let largest = CombatRule::Largest;
+        assert_eq!(largest.resolve(NEUTRAL, &[5, 8, 0]), (1, 3));
+        assert_eq!(largest.resolve(1, &[0, 4, 4]), (NEUTRAL, 0));
+
+        let bonus = CombatRule::NeutralBonus { ships: 3 };
+        assert_eq!(bonus.resolve(NEUTRAL, &[5, 8, 0]), (NEUTRAL, 0));
+        assert_eq!(bonus.resolve(NEUTRAL, &[5, 7, 0]), (NEUTRAL, 1));
+        assert_eq!(bonus.resolve(NEUTRAL, &[5, 0, 0]), (NEUTRAL, 5));
+        assert_eq!(bonus.resolve(NEUTRAL, &[5, 10, 0]), (1, 2));
+        assert_eq!(bonus.resolve(2, &[0, 5, 6]), (2, 1));
+    }
+}
@@ -1,8 +1,18 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{collections::BTreeMap, fmt::Display, sync::OnceLock};
 
+use serde::{Deserialize, Serialize};
 use vecs::Vec2;
 
-use super::{Expedition, Input, MoveOutput, Output, Owner, Planet, PlanetInput, PlanetStates};
+use crate::{
+    config::Config,
+    stats::{Stats, TurnStats},
+    value,
+};
+
+use super::{
+    Expedition, ExpeditionInput, Input, MoveOutput, Output, Owner, Plan, Planet, PlanetInput,
+    PlanetStates, PlannedSend, Symmetry, ThreatMap, MAX_HORIZON, MAX_SHIPS, ME, NEUTRAL,
+};
 
 struct PlanetFmt<'a> {
     state: &'a State,
@@ -19,6 +29,7 @@ impl<'a> Display for PlanetFmt<'a> {
     }
 }
 
+#[allow(unused)]
 struct ExpeditionFmt<'a> {
     state: &'a State,
     exp: &'a Expedition,
@@ -35,17 +46,31 @@ impl<'a> Display for ExpeditionFmt<'a> {
     }
 }
 
-type PlanetMap = HashMap<String, usize>;
+type PlanetMap = BTreeMap<String, usize>;
+
+/// `p` as the planner sees it. Owners past one player per planet would size every combat,
+/// and absurd ship counts overflow its sums, both are clamped.
+fn map_planet(p: &PlanetInput, id: usize, players: usize) -> Planet {
+    let owner = p.owner.unwrap_or_default();
+    let ships = p.ship_count.clamp(0, MAX_SHIPS);
+    let growth = p.growth.clamp(0, MAX_SHIPS);
+    if owner > players || ships != p.ship_count || growth != p.growth {
+        eprintln!(
+            "Clamping planet {:?} with owner {}, {} ships and growth {}",
+            p.name, owner, p.ship_count, p.growth
+        );
+    }
 
-fn map_planet(p: &PlanetInput, map: &PlanetMap) -> Planet {
     Planet {
-        id: map[&p.name],
-        ships: p.ship_count,
-        owner: p.owner.unwrap_or_default(),
+        id,
+        ships,
+        owner: owner.min(players),
         loc: Vec2::new(p.x, p.y),
+        growth,
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct State {
     planets: Vec<PlanetStates>,
 
@@ -55,48 +80,94 @@ pub struct State {
     handled_exps: u64,
 
     turns: Vec<(usize, usize, i32)>,
+    /// Ships per planet that moves may not use this turn
+    #[serde(default)]
+    reserved: Vec<i32>,
+    /// Accepted operations that still have sends waiting
+    #[serde(default)]
+    plans: Vec<Plan>,
+
+    symmetry: Option<Symmetry>,
+    /// Planets do not move, computed once
+    #[serde(skip)]
+    centrality: Vec<f32>,
+    /// Built on first use after the futures change
+    #[serde(skip)]
+    threats: OnceLock<ThreatMap>,
+    /// Enemy ships recently launched per planet, halved every turn
+    #[serde(default)]
+    launched: Vec<i32>,
+
+    #[serde(default)]
+    config: Config,
+
+    #[serde(default)]
+    stats: Stats,
 }
 
 #[allow(unused)]
 impl State {
     pub fn new(input: Input) -> Self {
+        Self::with_config(input, Config::default())
+    }
+
+    pub fn with_config(input: Input, config: Config) -> Self {
         let mut planets = Vec::new();
-        let mut planet_map = HashMap::new();
+        let mut planet_map = BTreeMap::new();
         let mut inv_planet_map = Vec::new();
 
+        let players = input.planets.len();
         for p in input.planets {
             let id = inv_planet_map.len();
             planet_map.insert(p.name.clone(), id);
             inv_planet_map.push(p.name.clone());
 
+            let planet = map_planet(&p, id, players);
             planets.push(Planet {
-                id,
-                ships: p.ship_count,
-                owner: p.owner.unwrap_or_default(),
-                loc: Vec2::new(p.x, p.y),
+                growth: config.rules.planet_growth(planet.growth),
+                ..planet
             });
         }
 
-        let max_dist = planets
+        let rules = config.rules;
+        let diameter = planets
             .iter()
             .flat_map(|p1| planets.iter().map(|p2| (p1.loc - p2.loc).length()))
-            .max_by(|x, y| x.total_cmp(&y))
-            .unwrap()
-            .ceil() as usize;
+            // Also catches the infinite and NaN distances of absurd coordinates
+            .map(|x| x.min(MAX_HORIZON as f32))
+            .max_by(|x, y| x.total_cmp(y))
+            .map_or(0, |x| rules.travel_time(x));
+        let horizon = config.horizon.unwrap_or(diameter).min(MAX_HORIZON);
 
-        let planet_count = planets.len();
-        let planet_states = planets
+        let symmetry = Symmetry::detect(&planets);
+
+        let planet_states: Vec<_> = planets
             .into_iter()
-            .map(|p| PlanetStates::new(p, planet_count, max_dist))
+            .map(|p| PlanetStates::new(p, horizon, rules))
             .collect();
+        let centrality = value::centrality(&planet_states);
 
-        Self {
+        let mut out = Self {
             planets: planet_states,
             planet_map,
             inv_planet_map,
             handled_exps: 0,
             turns: Vec::new(),
-        }
+            reserved: Vec::new(),
+            plans: Vec::new(),
+            symmetry,
+            centrality,
+            threats: OnceLock::new(),
+            launched: Vec::new(),
+            config,
+            stats: Stats::default(),
+        };
+
+        // Mid game snapshots already have expeditions underway
+        out.handle_expeditions(input.expeditions, true);
+        out.planets.iter_mut().for_each(|p| p.recalculate());
+
+        out
     }
 
     pub fn type_at<F: Fn(&Owner) -> bool + 'static>(
@@ -116,31 +187,153 @@ impl State {
             expeditions,
         }: Input,
     ) {
+        self.launched.iter_mut().for_each(|x| *x /= 2);
+        self.handle_expeditions(expeditions, false);
+
+        let mut changes = TurnStats::default();
+        for planet in planets {
+            let Some(&idx) = self.planet_map.get(&planet.name) else {
+                eprintln!("Ignoring unknown planet {:?}", planet.name);
+                continue;
+            };
+            let p = &mut self.planets[idx];
+
+            let before = p.planet;
+            let mut ours = p.arriving(ME);
+            if before.owner == ME {
+                ours += before.ships + before.growth;
+                self.stats.ships_produced += before.growth as i64;
+            }
+
+            p.turn();
+            p.flush(map_planet(&planet, idx, self.planet_map.len()));
+
+            let after = p.planet;
+            if after.owner == ME {
+                ours -= after.ships;
+            }
+            self.stats.ships_lost += ours.max(0) as i64;
+
+            match (before.owner == ME, after.owner == ME) {
+                (false, true) => changes.captured += 1,
+                (true, false) => changes.lost += 1,
+                _ => {}
+            }
+        }
+        self.stats.planets.push(changes);
+        self.threats = OnceLock::new();
+
+        let planets = &self.planets;
+        self.stats
+            .land_operations(|target| planets[target][0].owner == ME);
+
+        self.plans.iter_mut().for_each(Plan::tick);
+    }
+
+    /// Why the planner can not track expedition `e`, if it can not
+    fn invalid_expedition(&self, e: &ExpeditionInput) -> Option<&'static str> {
+        if !self.planet_map.contains_key(&e.origin) || !self.planet_map.contains_key(&e.destination)
+        {
+            Some("unknown planet")
+        } else if e.owner > self.planets.len() {
+            Some("unknown owner")
+        } else if e.turns_remaining >= MAX_HORIZON {
+            Some("arrives beyond the longest horizon")
+        } else {
+            None
+        }
+    }
+
+    /// Adds the arrivals of new expeditions. During a turn they are seen before the futures
+    /// rotate, and their ships leave the predicted origin. The `first` input is not rotated,
+    /// and its garrisons were observed without the ships in flight.
+    fn handle_expeditions(&mut self, expeditions: Vec<ExpeditionInput>, first: bool) {
         let exps = self.handled_exps;
 
-        for e in expeditions
-            .into_iter()
-            .filter(|e| e.id >= exps)
-            .map(|e| Expedition {
+        for e in expeditions.into_iter().filter(|e| e.id >= exps) {
+            if let Some(reason) = self.invalid_expedition(&e) {
+                eprintln!("Ignoring expedition {}: {}", e.id, reason);
+                self.handled_exps += 1;
+                continue;
+            }
+            let remaining = if first {
+                // Already landed, the observed planet has it
+                let Some(remaining) = e.turns_remaining.checked_sub(1) else {
+                    self.handled_exps += 1;
+                    continue;
+                };
+                remaining
+            } else {
+                e.turns_remaining
+            };
+
+            let e = Expedition {
                 id: e.id,
-                ships: e.ship_count,
+                ships: e.ship_count.clamp(0, MAX_SHIPS),
                 owner: e.owner,
-                remaining: e.turns_remaining,
+                remaining,
                 origin: self.planet_map[&e.origin],
                 destination: self.planet_map[&e.destination],
-            })
-        {
+            };
+            if e.remaining >= self.horizon() {
+                self.extend_horizon(e.remaining + 1);
+            }
+            if e.owner == ME {
+                self.stats.ships_sent += e.ships as i64;
+            } else if e.owner != NEUTRAL {
+                if self.launched.len() <= e.origin {
+                    self.launched.resize(e.origin + 1, 0);
+                }
+                self.launched[e.origin] += e.ships;
+            }
             self.planets[e.destination].incoming_exp(&e);
-            self.planets[e.origin].dispatch(e.ships);
+            if !first {
+                self.planets[e.origin].dispatch(e.ships);
+            }
             self.handled_exps += 1;
         }
+    }
 
-        for planet in planets {
-            let idx = self.planet_map[&planet.name];
-            let p = &mut self.planets[idx];
-            p.turn();
-            p.flush(map_planet(&planet, &self.planet_map))
+    /// Applies a move as if it was sent this turn and recalculates the affected futures.
+    /// Only useful on a scratch state, the real expedition shows up again next turn.
+    pub fn apply_move(&mut self, source: usize, target: usize, ships: i32) {
+        let distance = self.planets[source].distance(&self.planets[target]);
+        let exp = Expedition {
+            id: self.handled_exps,
+            ships,
+            // Seen from next turn, before its rotation
+            remaining: distance - 1,
+            owner: self.planets[source][0].owner,
+            origin: source,
+            destination: target,
+        };
+
+        // Every planet looks as far ahead, like for observed expeditions
+        if exp.remaining >= self.horizon() {
+            self.extend_horizon(exp.remaining + 1);
+        }
+        self.planets[target].incoming_exp(&exp);
+        self.planets[source].dispatch(ships);
+        self.planets[target].recalculate();
+        self.planets[source].recalculate();
+        self.threats = OnceLock::new();
+    }
+
+    /// Turns ahead the futures of every planet reach
+    pub fn horizon(&self) -> usize {
+        self.planets.first().map_or(0, PlanetStates::horizon)
+    }
+
+    /// Predicts every planet at least `horizon` turns ahead, up to `MAX_HORIZON`
+    pub fn extend_horizon(&mut self, horizon: usize) {
+        for p in &mut self.planets {
+            p.extend_horizon(horizon);
         }
+        self.threats = OnceLock::new();
+    }
+
+    pub fn planet_id(&self, name: &str) -> Option<usize> {
+        self.planet_map.get(name).copied()
     }
 
     fn fmt<'a>(&'a self, p: &'a Planet) -> PlanetFmt<'a> {
@@ -154,14 +347,224 @@ impl State {
         &self.planets
     }
 
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn turns_played(&self) -> usize {
+        self.stats.planets.len()
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn stats_mut(&mut self) -> &mut Stats {
+        &mut self.stats
+    }
+
+    /// Share of its garrison an enemy planet recently sent away, 0 when it did not
+    pub fn weakness(&self, planet: usize) -> f32 {
+        let current = self.planets[planet][0];
+        let launched = self.launched.get(planet).copied().unwrap_or_default();
+        if current.owner == ME || current.owner == NEUTRAL || launched <= 0 {
+            return 0.;
+        }
+        launched as f32 / (launched + current.ships.max(0)) as f32
+    }
+
+    pub fn threats(&self) -> &ThreatMap {
+        self.threats.get_or_init(|| ThreatMap::new(&self.planets))
+    }
+
+    /// Above 1 for planets closer to everything than the average planet
+    pub fn centrality(&self, planet: usize) -> f32 {
+        self.centrality[planet]
+    }
+
+    pub fn symmetry(&self) -> Option<&Symmetry> {
+        self.symmetry.as_ref()
+    }
+
     pub fn add_turn(&mut self, source: usize, target: usize, ships: i32) {
         self.turns.push((source, target, ships));
     }
 
+    /// Moves added this turn as source, target and ships
+    pub fn pending_moves(&self) -> &[(usize, usize, i32)] {
+        &self.turns
+    }
+
+    /// Serializes the complete internal state, including pending turns and predicted futures
+    pub fn snapshot(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_snapshot(snapshot: &str) -> serde_json::Result<Self> {
+        let mut state: Self = serde_json::from_str(snapshot)?;
+        state.centrality = value::centrality(&state.planets);
+        Ok(state)
+    }
+
+    /// Keeps `ships` on `planet` this turn, moves are clamped to leave them in place
+    pub fn reserve(&mut self, planet: usize, ships: i32) {
+        if self.reserved.len() <= planet {
+            self.reserved.resize(planet + 1, 0);
+        }
+        self.reserved[planet] += ships;
+    }
+
+    pub fn reserved(&self, planet: usize) -> i32 {
+        self.reserved.get(planet).copied().unwrap_or_default()
+    }
+
+    /// Ships of `planet` promised this turn, to its pending moves or to later plan sends
+    pub fn committed(&self, planet: usize) -> i32 {
+        let sent: i32 = self
+            .turns
+            .iter()
+            .filter(|(source, _, _)| *source == planet)
+            .map(|(_, _, ships)| ships)
+            .sum();
+        self.reserved(planet) + sent
+    }
+
+    pub fn plans(&self) -> &[Plan] {
+        &self.plans
+    }
+
+    pub fn add_plan(&mut self, plan: Plan) {
+        for send in &plan.sends {
+            self.reserve_send(send);
+        }
+        self.plans.push(plan);
+    }
+
+    /// Reserves the part of a later send that the source does not grow before it leaves
+    fn reserve_send(&mut self, send: &PlannedSend) {
+        let source = &self.planets[send.source];
+        let grown = source[send.delay].ships - source[0].ships;
+        let ships = (send.ships - grown.max(0)).max(0);
+        self.reserve(send.source, ships);
+    }
+
+    /// Sends what is due this turn, up to what the source holds, and reserves the ships
+    /// of later sends.
+    /// Sends from planets we no longer hold are dropped, plans that are no longer
+    /// needed or can no longer take their target are cancelled.
+    pub fn execute_plans(&mut self) {
+        let mut plans = std::mem::take(&mut self.plans);
+
+        plans.retain_mut(|plan| {
+            let name = &self.inv_planet_map[plan.target];
+            plan.sends
+                .retain(|send| self.planets[send.source][send.delay].owner == ME);
+
+            let target = self.planets[plan.target][plan.arrival];
+            if target.owner == ME {
+                eprintln!("Plan on {} is no longer needed", name);
+                return false;
+            }
+            if plan.pending() <= target.ships {
+                eprintln!(
+                    "Cancelling plan on {}, {} ships can not take {}",
+                    name,
+                    plan.pending(),
+                    target.ships
+                );
+                return false;
+            }
+
+            for send in &plan.sends {
+                if send.delay == 0 {
+                    // The source may hold fewer ships than predicted, it sends what it has
+                    let left = self.planets[send.source][0].ships - self.committed(send.source);
+                    let ships = send.ships.min(left);
+                    if ships > 0 {
+                        self.turns.push((send.source, plan.target, ships));
+                    }
+                } else {
+                    self.reserve_send(send);
+                }
+            }
+            plan.sends.retain(|send| send.delay > 0);
+
+            !plan.sends.is_empty()
+        });
+
+        self.plans = plans;
+    }
+
+    /// Validated moves of this turn, merged per source and target and clamped to the
+    /// ships each source has left after its reservation
+    pub fn take_moves(&mut self) -> Vec<(usize, usize, i32)> {
+        let reserved = std::mem::take(&mut self.reserved);
+
+        let mut moves: Vec<(usize, usize, i32)> = Vec::new();
+        for (source, target, ships) in std::mem::take(&mut self.turns) {
+            let drop_reason = if ships <= 0 {
+                Some("non positive ship count")
+            } else if source >= self.planets.len() || target >= self.planets.len() {
+                Some("unknown planet")
+            } else if source == target {
+                Some("origin equals destination")
+            } else if self.planets[source][0].owner != ME {
+                Some("origin is not ours")
+            } else {
+                None
+            };
+
+            if let Some(reason) = drop_reason {
+                let name = |id: usize| self.inv_planet_map.get(id).map_or("?", |x| x.as_str());
+                eprintln!(
+                    "Dropping move {} -> {} ({} ships): {}",
+                    name(source),
+                    name(target),
+                    ships,
+                    reason
+                );
+                continue;
+            }
+
+            match moves
+                .iter_mut()
+                .find(|(s, t, _)| *s == source && *t == target)
+            {
+                Some((_, _, total)) => *total += ships,
+                None => moves.push((source, target, ships)),
+            }
+        }
+
+        let mut available: Vec<i32> = self
+            .planets
+            .iter()
+            .enumerate()
+            .map(|(i, p)| p[0].ships - reserved.get(i).copied().unwrap_or_default())
+            .collect();
+
+        moves.retain_mut(|(source, target, ships)| {
+            let left = &mut available[*source];
+            if *ships > *left {
+                eprintln!(
+                    "Clamping move {} -> {} from {} to {} ships",
+                    self.inv_planet_map[*source],
+                    self.inv_planet_map[*target],
+                    ships,
+                    (*left).max(0)
+                );
+                *ships = (*left).max(0);
+            }
+            *left -= *ships;
+            *ships > 0
+        });
+
+        moves
+    }
+
     pub fn flush(&mut self) -> String {
         let moves: Vec<_> = self
-            .turns
-            .drain(..)
+            .take_moves()
+            .into_iter()
             .map(|(source, target, count)| {
                 let origin = &self.inv_planet_map[source];
                 let destination = &self.inv_planet_map[target];
@@ -182,7 +585,13 @@ impl State {
 
 #[cfg(test)]
 mod tests {
-    use crate::models::Input;
+    use std::time::Duration;
+
+    use crate::{
+        config::Config,
+        engine::{Game, Map, Move},
+        models::{recorded::*, Input, Plan, PlannedSend, MAX_HORIZON, MAX_SHIPS, ME},
+    };
 
     use super::State;
 
@@ -202,9 +611,285 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_apply_move() {
+        let mut state = state_at(HEX, 0);
+
+        let protos = state.planet_id("protos").unwrap();
+        let duteros = state.planet_id("duteros").unwrap();
+        state.apply_move(protos, duteros, 5);
+
+        let source = &state.planets()[protos];
+        let target = &state.planets()[duteros];
+        assert_eq!(source[0].ships, 1);
+        assert_eq!((target[5].owner, target[5].ships), (0, 6));
+        assert_eq!((target[6].owner, target[6].ships), (0, 1));
+    }
+
+    #[test]
+    fn test_starts_mid_game() {
+        let line = HEX.lines().next().unwrap();
+        let send = |origin: &str, destination: &str, ship_count| Move {
+            origin: origin.to_string(),
+            destination: destination.to_string(),
+            ship_count,
+        };
+        let mut game = Game::new(serde_json::from_str::<Map>(line).unwrap(), 100);
+        game.dispatch(1, &[send("protos", "duteros", 5)]);
+        game.dispatch(2, &[send("tetartos", "tritos", 4)]);
+        game.step();
+        let view = game.view(1);
+
+        let mut played = state_at(HEX, 0);
+        played.turn(serde_json::from_str(&view).unwrap());
+        let started = State::new(serde_json::from_str(&view).unwrap());
+
+        // Seen first or during the game, the fleets land the same
+        let futures = |state: &State| {
+            state
+                .planets()
+                .iter()
+                .map(|p| p.futures().map(|x| (x.owner, x.ships)).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(futures(&started), futures(&played));
+
+        // And where the engine lands them
+        for turn in 1..started.horizon() {
+            game.step();
+            for (p, planet) in started.planets().iter().zip(&game.map().planets) {
+                let expected = (planet.owner.unwrap_or_default(), planet.ship_count);
+                assert_eq!((p[turn].owner, p[turn].ships), expected, "turn {}", turn);
+            }
+        }
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let mut lines = HEX.lines();
+        let parse = |x: &str| serde_json::from_str::<Input>(x).unwrap();
+
+        let mut state = State::new(parse(lines.next().unwrap()));
+        for line in lines.by_ref().take(20) {
+            state.turn(parse(line));
+        }
+        state.add_turn(0, 1, 3);
+        // An operation on our home that lands after the restore
+        state.stats_mut().operation(0, 3);
+        state.stats_mut().planning(Duration::from_millis(10));
+
+        let mut restored = State::from_snapshot(&state.snapshot()).unwrap();
+        assert_eq!(restored.snapshot(), state.snapshot());
+
+        for line in lines {
+            state.turn(parse(line));
+            restored.turn(parse(line));
+            assert_eq!(restored.snapshot(), state.snapshot());
+        }
+        assert_eq!(restored.flush(), state.flush());
+
+        restored.stats_mut().planning(Duration::from_millis(30));
+        assert_eq!(restored.stats().operations_completed, 1);
+        assert_eq!(restored.stats().avg_planning_ms, 20.);
+    }
+
+    #[test]
+    fn test_move_validation() {
+        let mut state = state_at(HEX, 0);
+        let protos = state.planet_id("protos").unwrap();
+        let duteros = state.planet_id("duteros").unwrap();
+        let tritos = state.planet_id("tritos").unwrap();
+
+        state.add_turn(protos, duteros, 4);
+        state.add_turn(duteros, tritos, 3);
+        state.add_turn(protos, protos, 2);
+        state.add_turn(protos, tritos, -1);
+        state.add_turn(protos, duteros, 3);
+        assert_eq!(state.take_moves(), vec![(protos, duteros, 6)]);
+
+        state.reserve(protos, 2);
+        state.add_turn(protos, tritos, 3);
+        state.add_turn(protos, duteros, 3);
+        assert_eq!(
+            state.take_moves(),
+            vec![(protos, tritos, 3), (protos, duteros, 1)]
+        );
+
+        state.add_turn(protos, tritos, 6);
+        assert_eq!(state.take_moves(), vec![(protos, tritos, 6)]);
+    }
+
+    #[test]
+    fn test_plan_ledger() {
+        let mut lines = HEX.lines();
+        let parse = |x: &str| serde_json::from_str::<Input>(x).unwrap();
+        let mut state = State::new(parse(lines.next().unwrap()));
+        let protos = state.planet_id("protos").unwrap();
+        let duteros = state.planet_id("duteros").unwrap();
+        let tritos = state.planet_id("tritos").unwrap();
+
+        let plan = |ships| Plan {
+            target: duteros,
+            arrival: 7,
+            sends: vec![PlannedSend {
+                source: protos,
+                ships,
+                delay: 1,
+            }],
+        };
+
+        // Too small to ever take duteros
+        state.add_plan(plan(3));
+        state.execute_plans();
+        assert!(state.plans().is_empty());
+        state.take_moves();
+
+        // Protos grows one ship before it sends, the other six are reserved
+        state.add_plan(plan(7));
+        state.add_turn(protos, tritos, 6);
+        assert_eq!(state.take_moves(), vec![]);
+
+        state.turn(parse(lines.next().unwrap()));
+        state.execute_plans();
+        assert!(state.plans().is_empty());
+        assert_eq!(state.committed(protos), 7);
+        assert_eq!(state.take_moves(), vec![(protos, duteros, 7)]);
+
+        // A due send never asks for more than the source holds
+        let ships = state.planets()[protos][0].ships;
+        state.add_plan(Plan {
+            sends: vec![PlannedSend {
+                source: protos,
+                ships: 100,
+                delay: 0,
+            }],
+            ..plan(0)
+        });
+        // Last turn's reservations go with its moves
+        state.take_moves();
+        state.execute_plans();
+        assert_eq!(state.pending_moves(), [(protos, duteros, ships)]);
+    }
+
+    #[test]
+    fn test_weakness() {
+        let mut lines = HEX.lines();
+        let parse = |x: &str| serde_json::from_str::<Input>(x).unwrap();
+        let mut state = State::new(parse(lines.next().unwrap()));
+        let protos = state.planet_id("protos").unwrap();
+        let tetartos = state.planet_id("tetartos").unwrap();
+
+        // Tetartos sends 5 ships to protos and keeps 2 after growing
+        state.turn(parse(lines.next().unwrap()));
+        assert_eq!(state.weakness(tetartos), 5. / 7.);
+        assert_eq!(state.weakness(protos), 0.);
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        let parse = |x: &str| serde_json::from_str::<Input>(x).unwrap();
+        let mut state = State::new(parse(
+            r#"{"planets":[
+                {"ship_count":6,"x":0,"y":0,"owner":1,"name":"a"},
+                {"ship_count":6,"x":1e30,"y":0,"owner":2,"name":"b"}
+            ],"expeditions":[]}"#,
+        ));
+        assert_eq!(state.horizon(), MAX_HORIZON);
+
+        state.turn(parse(
+            r#"{"planets":[
+                {"ship_count":7,"x":0,"y":0,"owner":1,"name":"a"},
+                {"ship_count":7,"x":0,"y":0,"owner":1,"name":"nowhere"}
+            ],"expeditions":[
+                {"id":0,"ship_count":1,"origin":"a","destination":"nowhere","owner":1,"turns_remaining":3},
+                {"id":1,"ship_count":1,"origin":"a","destination":"b","owner":9,"turns_remaining":3},
+                {"id":2,"ship_count":1,"origin":"a","destination":"b","owner":1,"turns_remaining":5000}
+            ]}"#,
+        ));
+        assert_eq!(state.planets()[0][0].ships, 7);
+        assert!(state.planets()[1].futures().all(|p| p.owner == 2));
+
+        let state = State::new(parse(
+            r#"{"planets":[
+                {"ship_count":2147483647,"x":0,"y":0,"owner":1,"growth":2147483647,"name":"a"},
+                {"ship_count":6,"x":3,"y":0,"owner":18446744073709551615,"name":"b"}
+            ],"expeditions":[
+                {"id":0,"ship_count":2147483647,"origin":"a","destination":"b","owner":1,"turns_remaining":2}
+            ]}"#,
+        ));
+        assert_eq!(state.planets()[0][0].ships, MAX_SHIPS);
+        assert_eq!(state.planets()[1][0].owner, 2);
+        assert!(state.planets()[0].futures().all(|p| p.ships <= MAX_SHIPS));
+        crate::candidates(&state);
+    }
+
+    #[test]
+    fn test_horizon_grows() {
+        let config = Config {
+            horizon: Some(4),
+            ..Config::default()
+        };
+        let mut state = State::with_config(input_at(HEX, 0), config);
+        assert_eq!(state.horizon(), 4);
+
+        // So does a move of ours past it
+        let home = state.planet_id("protos").unwrap();
+        let (far, dist) = state
+            .planets()
+            .iter()
+            .map(|p| (p.id(), p.distance(&state.planets()[home])))
+            .max_by_key(|x| x.1)
+            .unwrap();
+        assert!(dist > 4);
+        let mut moved = state.clone();
+        moved.apply_move(home, far, 1);
+        assert!(moved.planets().iter().all(|p| p.horizon() == dist));
+
+        // A fleet past the horizon makes every planet look further ahead
+        let mut next = input_at(HEX, 0);
+        next.expeditions = serde_json::from_str(
+            r#"[{"id":0,"ship_count":30,"origin":"tetartos","destination":"protos","owner":2,"turns_remaining":20}]"#,
+        )
+        .unwrap();
+        state.turn(next);
+        assert_eq!(state.horizon(), 21);
+        assert!(state.planets().iter().all(|p| p.horizon() == 21));
+
+        let protos = &state.planets()[state.planet_id("protos").unwrap()];
+        assert_eq!((protos[19].owner, protos[19].ships), (ME, 25));
+        assert_eq!((protos[20].owner, protos[20].ships), (2, 4));
+    }
+
+    #[test]
+    fn test_ships_lost() {
+        let turn = |home: i32, outpost: (usize, i32), expeditions: &str| {
+            let input = format!(
+                r#"{{"planets":[
+                    {{"ship_count":{},"x":0,"y":0,"owner":1,"name":"home"}},
+                    {{"ship_count":{},"x":0,"y":4,"owner":{},"name":"outpost"}},
+                    {{"ship_count":50,"x":10,"y":0,"owner":2,"name":"enemy"}}
+                ],"expeditions":[{}]}}"#,
+                home, outpost.1, outpost.0, expeditions
+            );
+            serde_json::from_str::<Input>(&input).unwrap()
+        };
+        let mut state = State::new(turn(
+            20,
+            (ME, 3),
+            r#"{"id":0,"ship_count":8,"origin":"enemy","destination":"home","owner":2,"turns_remaining":1},
+               {"id":1,"ship_count":10,"origin":"enemy","destination":"outpost","owner":2,"turns_remaining":1}"#,
+        ));
+
+        // Home grows to 21 and loses 8, the outpost grows to 4 and is taken
+        state.turn(turn(13, (2, 6), ""));
+        assert_eq!(state.stats().ships_produced, 2);
+        assert_eq!(state.stats().ships_lost, 8 + 4);
+        assert_eq!(state.stats().planets[0].lost, 1);
+    }
+
     #[test]
     fn test_hex() {
-        test_states(include_str!("../../tests/success_hex.txt"));
+        test_states(HEX);
     }
 
     #[test]
@@ -214,6 +899,6 @@ mod tests {
 
     #[test]
     fn test_spiral() {
-        test_states(include_str!("../../tests/success_spiral.txt"));
+        test_states(SPIRAL);
     }
 }
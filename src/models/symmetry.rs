@@ -0,0 +1,189 @@
+use std::f32::consts::PI;
+
+use vecs::Vec2;
+
+use serde::{Deserialize, Serialize};
+
+use super::{coords, Owner, Planet, ME, NEUTRAL};
+
+/// Maps are hand written with rounded coordinates, allow some slack when matching.
+const EPSILON: f32 = 0.05;
+const MAX_ROTATION_ORDER: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SymmetryKind {
+    /// Rotation over `2π / order` around the center of the map
+    Rotation { order: usize },
+    /// Reflection over the line through the center with angle `angle`
+    Mirror { angle: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Ours,
+    Theirs,
+    Center,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Symmetry {
+    pub kind: SymmetryKind,
+    sides: Vec<Side>,
+}
+
+impl SymmetryKind {
+    fn apply(&self, center: Vec2<f32>, loc: Vec2<f32>) -> Vec2<f32> {
+        let (dx, dy) = coords(loc - center);
+        let (x, y) = match *self {
+            SymmetryKind::Rotation { order } => {
+                let (sin, cos) = (2. * PI / order as f32).sin_cos();
+                (dx * cos - dy * sin, dx * sin + dy * cos)
+            }
+            SymmetryKind::Mirror { angle } => {
+                let (sin, cos) = (2. * angle).sin_cos();
+                (dx * cos + dy * sin, dx * sin - dy * cos)
+            }
+        };
+        center + Vec2::new(x, y)
+    }
+
+    fn mapping(&self, center: Vec2<f32>, planets: &[Planet]) -> Option<Vec<usize>> {
+        let mut taken = vec![false; planets.len()];
+        let mut mirror = Vec::with_capacity(planets.len());
+
+        for p in planets {
+            let loc = self.apply(center, p.loc);
+            let other = planets
+                .iter()
+                .position(|o| (o.loc - loc).length() < EPSILON)
+                .filter(|&o| !taken[o])?;
+            taken[other] = true;
+            mirror.push(other);
+        }
+
+        Some(mirror)
+    }
+}
+
+impl Symmetry {
+    /// Detects rotational or mirror symmetry in the planet locations.
+    /// Only symmetries that map our home onto an enemy home are accepted, the first one wins.
+    pub fn detect(planets: &[Planet]) -> Option<Self> {
+        if planets.len() < 2 {
+            return None;
+        }
+
+        let center =
+            planets.iter().fold(Vec2::new(0., 0.), |acc, p| acc + p.loc) / planets.len() as f32;
+
+        let mut kinds: Vec<_> = (2..=MAX_ROTATION_ORDER)
+            .map(|order| SymmetryKind::Rotation { order })
+            .collect();
+
+        // A mirror axis either goes through the first planet or halfway between it and its image
+        let (x0, y0) = coords(planets[0].loc - center);
+        kinds.push(SymmetryKind::Mirror {
+            angle: y0.atan2(x0),
+        });
+        for p in &planets[1..] {
+            let (x, y) = coords(p.loc - center);
+            kinds.push(SymmetryKind::Mirror {
+                angle: (y + y0).atan2(x + x0),
+            });
+        }
+
+        let is_enemy = |o: Owner| o != ME && o != NEUTRAL;
+        let home = planets.iter().find(|p| p.owner == ME);
+
+        let mut found = kinds
+            .into_iter()
+            .filter_map(|kind| Some((kind, kind.mapping(center, planets)?)));
+
+        let (kind, mirror) = match home {
+            Some(home) => found.find(|(_, mirror)| is_enemy(planets[mirror[home.id]].owner))?,
+            None => found.next()?,
+        };
+
+        let sides = planets
+            .iter()
+            .map(|p| match home {
+                Some(home) => {
+                    let ours = (p.loc - home.loc).length();
+                    let theirs = (p.loc - planets[mirror[home.id]].loc).length();
+                    if (ours - theirs).abs() < EPSILON {
+                        Side::Center
+                    } else if ours < theirs {
+                        Side::Ours
+                    } else {
+                        Side::Theirs
+                    }
+                }
+                None => Side::Center,
+            })
+            .collect();
+
+        Some(Self { kind, sides })
+    }
+
+    /// Whose half of the map `planet` lies on, judged from the starting positions
+    pub fn side(&self, planet: usize) -> Side {
+        self.sides[planet]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{recorded::*, State};
+
+    use super::{Side, SymmetryKind};
+
+    fn name(state: &State, id: usize) -> &str {
+        &state.inv_planet_map[id]
+    }
+
+    #[test]
+    fn hex_is_point_symmetric() {
+        let state = state_at(HEX, 0);
+        let symmetry = state.symmetry().unwrap();
+
+        assert_eq!(symmetry.kind, SymmetryKind::Rotation { order: 2 });
+        let sides: Vec<_> = (0..6)
+            .map(|id| (name(&state, id), symmetry.side(id)))
+            .collect();
+        assert_eq!(
+            sides,
+            vec![
+                ("protos", Side::Ours),
+                ("duteros", Side::Ours),
+                ("tritos", Side::Theirs),
+                ("tetartos", Side::Theirs),
+                ("pemptos", Side::Theirs),
+                ("extos", Side::Ours),
+            ]
+        );
+    }
+
+    #[test]
+    fn spiral_maps_home_onto_enemy() {
+        let state = state_at(SPIRAL, 0);
+        let symmetry = state.symmetry().unwrap();
+
+        assert_eq!(symmetry.kind, SymmetryKind::Rotation { order: 2 });
+        assert_eq!(name(&state, 6), "1-6");
+        assert_eq!(symmetry.side(6), Side::Ours);
+        assert_eq!(symmetry.side(state.planet_id("3-6").unwrap()), Side::Theirs);
+        assert_eq!(symmetry.side(0), Side::Center);
+    }
+
+    #[test]
+    fn needs_an_enemy_across() {
+        let input = r#"{"planets":[
+            {"ship_count":10,"x":0,"y":0,"owner":1,"name":"home"},
+            {"ship_count":5,"x":2,"y":0,"owner":null,"name":"neutral"},
+            {"ship_count":10,"x":1,"y":3,"owner":2,"name":"enemy"}
+        ],"expeditions":[]}"#;
+        let state = State::new(serde_json::from_str(input).unwrap());
+
+        assert!(state.symmetry().is_none());
+    }
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+use super::{PlanetStates, ME, NEUTRAL};
+
+/// Worst case enemy force per planet and turn: every enemy garrison that can reach
+/// the planet in time is assumed to be launched at it, growth included
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThreatMap {
+    threats: Vec<Vec<i32>>,
+}
+
+impl ThreatMap {
+    pub fn new(planets: &[PlanetStates]) -> Self {
+        let threats = planets
+            .iter()
+            .map(|target| {
+                let horizon = target.horizon();
+                let mut threat = vec![0; horizon + 1];
+
+                for enemy in planets.iter().filter(|x| x.id() != target.id()) {
+                    let dist = enemy.distance(target);
+                    for (turn, t) in threat.iter_mut().enumerate().skip(dist.max(1)) {
+                        let garrison = &enemy[turn - dist];
+                        if garrison.owner != ME && garrison.owner != NEUTRAL {
+                            *t += garrison.ships;
+                        }
+                    }
+                }
+
+                threat
+            })
+            .collect();
+
+        Self { threats }
+    }
+
+    /// Enemy ships that can have landed on `planet` by `turn`, later turns count as the last
+    pub fn at(&self, planet: usize, turn: usize) -> i32 {
+        self.threats
+            .get(planet)
+            .and_then(|x| x.get(turn.min(x.len().saturating_sub(1))))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ThreatMap;
+    use crate::models::{recorded::*, State};
+
+    #[test]
+    fn garrisons_reach_in_time() {
+        let state = state_at(HEX, 0);
+        let protos = state.planet_id("protos").unwrap();
+        let tetartos = state.planet_id("tetartos").unwrap();
+
+        let threats = ThreatMap::new(state.planets());
+        // Tetartos is 12 away, at the horizon of the map
+        assert_eq!(threats.at(protos, 11), 0);
+        assert_eq!(threats.at(protos, 12), 6);
+        assert_eq!(threats.at(protos, 50), 6);
+        // Only planets of ours are in the way
+        assert_eq!(threats.at(tetartos, 5), 0);
+    }
+
+    #[test]
+    fn garrisons_add_up() {
+        let input = r#"{"planets":[
+            {"ship_count":10,"x":0,"y":0,"owner":1,"name":"home"},
+            {"ship_count":10,"x":2,"y":0,"owner":2,"name":"near"},
+            {"ship_count":10,"x":0,"y":4,"owner":2,"name":"far"}
+        ],"expeditions":[]}"#;
+        let state = State::new(serde_json::from_str(input).unwrap());
+
+        let threats = ThreatMap::new(state.planets());
+        assert_eq!(threats.at(0, 2), 10);
+        // Near has grown by two ships by the time far reaches home as well
+        assert_eq!(threats.at(0, 4), 12 + 10);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::models::*;
+
+/// Number of turns the opening plan looks ahead.
+pub const OPENING_HORIZON: usize = 30;
+/// Neutrals the enemy reaches less than this many turns after us are left alone.
+const SNIPE_MARGIN: usize = 2;
+/// Only the closest safe neutrals are considered, the search is exhaustive.
+const MAX_CANDIDATES: usize = 7;
+
+#[derive(Debug)]
+struct Candidate<'a> {
+    target: &'a PlanetStates,
+    dist: usize,
+}
+
+impl<'a> Candidate<'a> {
+    fn cost(&self, send: usize) -> i32 {
+        future_at(self.target, send + self.dist).ships + 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlannedCapture {
+    pub target: usize,
+    pub send_turn: usize,
+    pub arrival: usize,
+    pub cost: i32,
+    pub growth: i32,
+}
+
+#[derive(Debug, Default)]
+pub struct OpeningPlan {
+    pub captures: Vec<PlannedCapture>,
+    /// Ships we expect to own at the horizon when following this plan
+    pub ships: i32,
+}
+
+fn future_at(planet: &PlanetStates, turn: usize) -> &Planet {
+    planet
+        .iter_from(turn)
+        .next()
+        .unwrap_or_else(|| planet.futures().last().unwrap())
+}
+
+fn usable_ships(planet: &PlanetStates) -> i32 {
+    planet
+        .futures()
+        .take(OPENING_HORIZON)
+        .map(|x| if x.owner == ME { x.ships } else { -x.ships })
+        .min()
+        .unwrap()
+        - 1
+}
+
+/// Ships available to spend at `turn` when everything in `captures` is sent.
+fn spendable(base: i32, growth: i32, captures: &[PlannedCapture], turn: usize) -> i32 {
+    captures.iter().fold(base + growth * turn as i32, |acc, c| {
+        let garrison = if c.arrival <= turn {
+            1 + c.growth * (turn - c.arrival) as i32
+        } else {
+            0
+        };
+        acc - c.cost + garrison
+    })
+}
+
+struct Search<'a> {
+    candidates: Vec<Candidate<'a>>,
+    base: i32,
+    growth: i32,
+    horizon: usize,
+
+    used: Vec<bool>,
+    current: Vec<PlannedCapture>,
+    best: OpeningPlan,
+}
+
+impl<'a> Search<'a> {
+    fn run(&mut self, earliest: usize) {
+        let ships = spendable(self.base, self.growth, &self.current, self.horizon);
+        if ships > self.best.ships {
+            self.best = OpeningPlan {
+                captures: self.current.clone(),
+                ships,
+            };
+        }
+
+        for i in 0..self.candidates.len() {
+            if self.used[i] {
+                continue;
+            }
+
+            let candidate = &self.candidates[i];
+            let Some(send_turn) =
+                (earliest..self.horizon.saturating_sub(candidate.dist)).find(|&t| {
+                    spendable(self.base, self.growth, &self.current, t) >= candidate.cost(t)
+                })
+            else {
+                continue;
+            };
+
+            self.used[i] = true;
+            self.current.push(PlannedCapture {
+                target: candidate.target.id(),
+                send_turn,
+                arrival: send_turn + candidate.dist,
+                cost: candidate.cost(send_turn),
+                growth: candidate.target.planet.growth,
+            });
+
+            self.run(send_turn);
+
+            self.current.pop();
+            self.used[i] = false;
+        }
+    }
+}
+
+/// Chooses which neutrals to take, and in what order, to maximize the ships we own at
+/// `horizon`. Captured neutrals pay back their growth every turn, every ship spent is gone.
+pub fn plan_opening(state: &State, horizon: usize) -> OpeningPlan {
+    let ours: Vec<_> = state.type_at(0, |o| *o == ME).collect();
+    let enemies: Vec<_> = state.type_at(0, |o| *o != ME && *o != NEUTRAL).collect();
+
+    let min_dist = |from: &[&PlanetStates], to: &PlanetStates| {
+        from.iter()
+            .map(|p| p.distance(to))
+            .min()
+            .unwrap_or(usize::MAX)
+    };
+
+    let mut candidates: Vec<_> = state
+        .type_at(0, |o| *o == NEUTRAL)
+        .filter(|p| p.futures().all(|f| f.owner == NEUTRAL))
+        .map(|target| Candidate {
+            target,
+            dist: min_dist(&ours, target),
+        })
+        .filter(|c| min_dist(&enemies, c.target) >= c.dist.saturating_add(SNIPE_MARGIN))
+        .filter(|c| {
+            state
+                .symmetry()
+                .is_none_or(|s| s.side(c.target.id()) == Side::Ours)
+        })
+        .collect();
+    candidates.sort_by_key(|c| c.dist);
+    candidates.truncate(MAX_CANDIDATES);
+
+    let base = ours.iter().map(|p| usable_ships(p).max(0)).sum();
+    let mut search = Search {
+        used: vec![false; candidates.len()],
+        candidates,
+        base,
+        growth: ours.iter().map(|p| p.planet.growth).sum(),
+        horizon,
+        current: Vec::new(),
+        best: OpeningPlan {
+            captures: Vec::new(),
+            ships: i32::MIN,
+        },
+    };
+    search.run(0);
+
+    search.best
+}
+
+/// Executes the part of the opening plan that is due this turn.
+/// Returns false when the opening sends nothing this turn.
+pub fn opening(state: &mut State) -> bool {
+    let plan = plan_opening(state, OPENING_HORIZON);
+
+    let mut sources: Vec<_> = state
+        .type_at(0, |o| *o == ME)
+        .map(|p| (p.id(), usable_ships(p) - state.committed(p.id())))
+        .collect();
+
+    let mut sent = false;
+    for capture in plan.captures.iter().filter(|c| c.send_turn == 0) {
+        if sources
+            .iter()
+            .map(|(_, ships)| (*ships).max(0))
+            .sum::<i32>()
+            < capture.cost
+        {
+            continue;
+        }
+
+        let target = &state.planets()[capture.target];
+        sources.sort_by_key(|(id, _)| state.planets()[*id].distance(target));
+
+        let mut required = capture.cost;
+        let mut sends = Vec::new();
+        for (id, ships) in sources.iter_mut() {
+            let send = required.min(*ships);
+            if send > 0 {
+                *ships -= send;
+                required -= send;
+                sends.push((*id, send));
+            }
+        }
+
+        for (source, ships) in sends {
+            state.add_turn(source, capture.target, ships);
+            sent = true;
+        }
+    }
+
+    sent
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::recorded::*;
+
+    use super::{opening, plan_opening, OPENING_HORIZON};
+
+    #[test]
+    fn hex_expands_on_own_side() {
+        let mut state = state_at(HEX, 0);
+
+        let plan = plan_opening(&state, OPENING_HORIZON);
+        let mut targets: Vec<_> = plan
+            .captures
+            .iter()
+            .map(|c| state.inv_planet_map[c.target].as_str())
+            .collect();
+        targets.sort();
+
+        assert_eq!(targets, vec!["duteros", "extos"]);
+        assert_eq!(plan.captures[0].send_turn, 2);
+
+        // Nothing is due yet, the regular planner has to run this turn
+        assert!(!opening(&mut state));
+        assert!(state.pending_moves().is_empty());
+    }
+}
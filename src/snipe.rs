@@ -0,0 +1,170 @@
+use crate::{
+    hold_check, models::*, usable_planets, value::planet_value, EnemyResponse, OptionalOperation,
+};
+
+/// First turn `planet` changes from neutral to `owner`, as predicted by its futures
+fn capture_by(planet: &PlanetStates, owner: impl Fn(Owner) -> bool) -> Option<usize> {
+    let futures: Vec<_> = planet.futures().collect();
+    futures
+        .windows(2)
+        .position(|w| w[0].owner == NEUTRAL && owner(w[1].owner))
+        .map(|i| i + 1)
+}
+
+fn is_enemy(owner: Owner) -> bool {
+    owner != ME && owner != NEUTRAL
+}
+
+/// An attack that lands on a neutral the turn after the enemy takes it, when the
+/// enemy garrison is what survived the fight with the neutral one
+pub fn find_snipe(target: &PlanetStates, state: &State) -> Option<OptionalOperation> {
+    if target[0].owner != NEUTRAL {
+        return None;
+    }
+
+    let arrival = capture_by(target, is_enemy)? + 1;
+    if arrival > target.horizon()
+        || !is_enemy(target[arrival].owner)
+        || target.iter_from(arrival).any(|x| x.owner == ME)
+    {
+        return None;
+    }
+
+    let options: Vec<_> = state
+        .planets()
+        .iter()
+        .map(|p| (p, p.distance(target)))
+        .filter(|(_, d)| *d <= arrival)
+        .collect();
+
+    let response = EnemyResponse::new(target, arrival, state);
+    let required_ships = target[arrival].ships + 1 + response.reinforce;
+    let usable_planets = usable_planets(&options, arrival, state);
+    let offered: i32 = usable_planets.iter().map(|x| x.usable_ships).sum();
+
+    if offered <= required_ships {
+        return None;
+    }
+
+    let value = planet_value(
+        target,
+        target[arrival].owner,
+        arrival,
+        required_ships,
+        state,
+    );
+    let (required_ships, value) = hold_check(required_ships, &response, offered, value, state);
+    // Too late in the game to pay back what the snipe costs
+    if value <= 0. {
+        return None;
+    }
+    Some(OptionalOperation::new(
+        target.id(),
+        arrival,
+        required_ships,
+        usable_planets,
+        value,
+        state,
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnipeRisk {
+    pub planet: usize,
+    /// Turn our fleets take the neutral
+    pub capture: usize,
+    pub garrison: i32,
+    /// Enemy ships that can land the turn after
+    pub threat: i32,
+}
+
+/// Neutrals we are about to take that the enemy can take from us right after
+pub fn snipe_risks(state: &State) -> Vec<SnipeRisk> {
+    state
+        .planets()
+        .iter()
+        .filter(|p| p[0].owner == NEUTRAL)
+        .filter_map(|p| {
+            let capture = capture_by(p, |o| o == ME)?;
+            let after = (capture + 1).min(p.horizon());
+            let risk = SnipeRisk {
+                planet: p.id(),
+                capture,
+                garrison: p[after].ships,
+                threat: state.threats().at(p.id(), after),
+            };
+            (p[after].owner == ME && risk.threat > risk.garrison).then_some(risk)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_snipe, snipe_risks};
+    use crate::{candidates, config::Config, models::*, EnemyResponse};
+
+    fn state(expedition: &str) -> State {
+        state_with(expedition, Config::default())
+    }
+
+    fn state_with(expedition: &str, config: Config) -> State {
+        let input = format!(
+            r#"{{"planets":[
+                {{"ship_count":40,"x":0,"y":0,"owner":1,"name":"home"}},
+                {{"ship_count":5,"x":2,"y":0,"owner":null,"name":"near"}},
+                {{"ship_count":5,"x":6,"y":0,"owner":null,"name":"far"}},
+                {{"ship_count":30,"x":10,"y":0,"owner":2,"name":"enemy"}}
+            ],"expeditions":[{}]}}"#,
+            expedition
+        );
+        State::with_config(serde_json::from_str(&input).unwrap(), config)
+    }
+
+    #[test]
+    fn snipes_after_the_enemy_capture() {
+        let state = state(
+            r#"{"id":0,"ship_count":8,"origin":"enemy","destination":"near","owner":2,"turns_remaining":3}"#,
+        );
+        let near = &state.planets()[state.planet_id("near").unwrap()];
+        let capture = (0..).find(|&i| near[i].owner == 2).unwrap();
+
+        let snipe = find_snipe(near, &state).unwrap();
+        assert_eq!(snipe.duration, capture + 1);
+        // Home can spare enough to also hold it against the enemy garrison
+        let hold = EnemyResponse::new(near, capture + 1, &state).hold;
+        assert!(hold > 0);
+        assert_eq!(snipe.required_ships, near[capture + 1].ships + 1 + hold);
+
+        // Taking the neutral before the small enemy fleet lands, and holding it, scores better
+        let (candidates, _) = candidates(&state);
+        let chosen = candidates.iter().find(|x| x.target == near.id()).unwrap();
+        assert!(chosen.duration < capture);
+        assert!(chosen.score() > snipe.score());
+    }
+
+    #[test]
+    fn skips_snipes_without_value() {
+        let expedition = r#"{"id":0,"ship_count":8,"origin":"enemy","destination":"near","owner":2,"turns_remaining":3}"#;
+        let config = Config {
+            game_length: 4,
+            ..Config::default()
+        };
+        let state = state_with(expedition, config);
+        let near = &state.planets()[state.planet_id("near").unwrap()];
+
+        assert!(find_snipe(near, &state).is_none());
+    }
+
+    #[test]
+    fn warns_about_our_captures_in_enemy_reach() {
+        let state = state(
+            r#"{"id":0,"ship_count":7,"origin":"home","destination":"far","owner":1,"turns_remaining":3}"#,
+        );
+        let far = state.planet_id("far").unwrap();
+
+        let risks = snipe_risks(&state);
+        assert_eq!(risks.len(), 1);
+        assert_eq!(risks[0].planet, far);
+        assert!(risks[0].threat >= 30);
+    }
+}
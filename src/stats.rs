@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Planets that changed hands in one turn
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TurnStats {
+    pub captured: usize,
+    pub lost: usize,
+}
+
+/// Summary of a played game, written as json when the input ends
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Stats {
+    pub ships_produced: i64,
+    pub ships_sent: i64,
+    pub ships_lost: i64,
+    pub planets: Vec<TurnStats>,
+    pub operations_attempted: usize,
+    pub operations_completed: usize,
+    pub max_planning_ms: f64,
+    pub avg_planning_ms: f64,
+    /// Targets of attempted operations with the turns until they land
+    #[serde(default)]
+    pending_operations: Vec<(usize, usize)>,
+    #[serde(default)]
+    planned_turns: usize,
+}
+
+/// The part of `Stats` the json summary shows, snapshots keep all of it
+#[derive(Serialize)]
+struct Summary<'a> {
+    ships_produced: i64,
+    ships_sent: i64,
+    ships_lost: i64,
+    planets: &'a [TurnStats],
+    operations_attempted: usize,
+    operations_completed: usize,
+    max_planning_ms: f64,
+    avg_planning_ms: f64,
+}
+
+impl Stats {
+    pub fn operation(&mut self, target: usize, arrival: usize) {
+        self.operations_attempted += 1;
+        self.pending_operations.push((target, arrival));
+    }
+
+    /// Ticks the attempted operations, `owned` tells whether we hold a planet now
+    pub fn land_operations(&mut self, owned: impl Fn(usize) -> bool) {
+        let mut completed = 0;
+        self.pending_operations.retain_mut(|(target, arrival)| {
+            *arrival = arrival.saturating_sub(1);
+            if *arrival > 0 {
+                return true;
+            }
+            if owned(*target) {
+                completed += 1;
+            }
+            false
+        });
+        self.operations_completed += completed;
+    }
+
+    pub fn planning(&mut self, took: Duration) {
+        let ms = took.as_secs_f64() * 1000.;
+        self.max_planning_ms = self.max_planning_ms.max(ms);
+        self.avg_planning_ms = (self.avg_planning_ms * self.planned_turns as f64 + ms)
+            / (self.planned_turns + 1) as f64;
+        self.planned_turns += 1;
+    }
+
+    pub fn to_json(&self) -> String {
+        let summary = Summary {
+            ships_produced: self.ships_produced,
+            ships_sent: self.ships_sent,
+            ships_lost: self.ships_lost,
+            planets: &self.planets,
+            operations_attempted: self.operations_attempted,
+            operations_completed: self.operations_completed,
+            max_planning_ms: self.max_planning_ms,
+            avg_planning_ms: self.avg_planning_ms,
+        };
+        serde_json::to_string_pretty(&summary).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Stats;
+
+    #[test]
+    fn operations_complete_when_they_land_on_ours() {
+        let mut stats = Stats::default();
+        stats.operation(3, 2);
+        stats.operation(4, 1);
+
+        stats.land_operations(|_| false);
+        stats.land_operations(|x| x == 3);
+        assert_eq!(
+            (stats.operations_attempted, stats.operations_completed),
+            (2, 1)
+        );
+
+        stats.planning(Duration::from_millis(10));
+        stats.planning(Duration::from_millis(30));
+        assert_eq!(stats.max_planning_ms, 30.);
+        assert_eq!(stats.avg_planning_ms, 20.);
+
+        let json = stats.to_json();
+        assert!(!json.contains("pending_operations"));
+        assert!(!json.contains("planned_turns"));
+    }
+}
@@ -0,0 +1,53 @@
+use std::{
+    env,
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use serde::Serialize;
+
+use crate::explain::Explanation;
+
+/// Something worth remembering about a turn, written as one json line to the trace file
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TraceEvent {
+    PlannerFailed {
+        turn: usize,
+        reason: String,
+        fallback: &'static str,
+    },
+    Explanations {
+        turn: usize,
+        candidates: Vec<Explanation>,
+    },
+}
+
+/// Collects trace events in the file named by `SILVIATOR_TRACE`, failures are always logged
+#[derive(Default)]
+pub struct Trace {
+    out: Option<BufWriter<File>>,
+}
+
+impl Trace {
+    pub fn from_env() -> Self {
+        let out = env::var("SILVIATOR_TRACE")
+            .ok()
+            .and_then(|path| File::create(path).ok())
+            .map(BufWriter::new);
+
+        Self { out }
+    }
+
+    pub fn record(&mut self, event: TraceEvent) {
+        if let TraceEvent::PlannerFailed { .. } = event {
+            eprintln!("Trace: {:?}", event);
+        }
+
+        if let Some(out) = self.out.as_mut() {
+            let line = serde_json::to_string(&event).unwrap();
+            // Losing the trace is no reason to lose the game
+            let _ = writeln!(out, "{}", line).and_then(|_| out.flush());
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::*;
+
+/// Turns into an operation the value of its target, the biggest score is tried first
+pub trait ScoreModel {
+    fn score(&self, value: f32, duration: usize, required_ships: i32) -> f32;
+}
+
+/// Value over the squared ships times turns, strongly prefers small and close targets
+pub struct Squared;
+
+impl ScoreModel for Squared {
+    fn score(&self, value: f32, duration: usize, required_ships: i32) -> f32 {
+        value / (duration as i32 * required_ships).pow(2) as f32
+    }
+}
+
+/// Value per turn waited for it
+pub struct Rate;
+
+impl ScoreModel for Rate {
+    fn score(&self, value: f32, duration: usize, _: i32) -> f32 {
+        value / duration.max(1) as f32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scoring {
+    Squared,
+    Rate,
+}
+
+impl Scoring {
+    pub fn model(self) -> &'static dyn ScoreModel {
+        match self {
+            Scoring::Squared => &Squared,
+            Scoring::Rate => &Rate,
+        }
+    }
+}
+
+impl FromStr for Scoring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "squared" => Ok(Scoring::Squared),
+            "rate" => Ok(Scoring::Rate),
+            _ => Err(format!("Unknown scoring {:?}", s)),
+        }
+    }
+}
+
+fn mean_distance(planet: &PlanetStates, planets: &[PlanetStates]) -> f32 {
+    let total: f32 = planets.iter().map(|p| p.distance(planet) as f32).sum();
+    total / (planets.len() - 1).max(1) as f32
+}
+
+fn nearest(planet: &PlanetStates, state: &State, owner: impl Fn(Owner) -> bool) -> Option<usize> {
+    state
+        .planets()
+        .iter()
+        .filter(|p| p.id() != planet.id() && owner(p[0].owner))
+        .map(|p| p.distance(planet))
+        .min()
+}
+
+/// Per planet, above 1 for planets closer to everything than the average planet
+pub(crate) fn centrality(planets: &[PlanetStates]) -> Vec<f32> {
+    let means: Vec<f32> = planets.iter().map(|p| mean_distance(p, planets)).collect();
+    let map = means.iter().sum::<f32>() / planets.len().max(1) as f32;
+    means.iter().map(|x| map.max(1.) / x.max(1.)).collect()
+}
+
+/// 1 halfway between our planets and the enemy's, up to 2 deep in our territory
+fn frontier(planet: &PlanetStates, state: &State) -> f32 {
+    let ours = nearest(planet, state, |o| o == ME);
+    let theirs = nearest(planet, state, |o| o != ME && o != NEUTRAL);
+    match (ours, theirs) {
+        (Some(ours), Some(theirs)) => 2. * (theirs + 1) as f32 / (ours + theirs + 2) as f32,
+        _ => 1.,
+    }
+}
+
+/// Ships that owning `target` from `arrival` until the game ends is worth, when it has
+/// `owner` before and the capture costs `cost` ships.
+/// Taking it from another player counts double, they lose the production we gain.
+pub fn planet_value(
+    target: &PlanetStates,
+    owner: Owner,
+    arrival: usize,
+    cost: i32,
+    state: &State,
+) -> f32 {
+    let remaining = state
+        .config()
+        .game_length
+        .saturating_sub(state.turns_played() + arrival);
+    let swing = if owner == NEUTRAL { 1. } else { 2. };
+    let production = swing * (target.planet.growth * remaining as i32) as f32;
+    let value = production * state.centrality(target.id()) * frontier(target, state) - cost as f32;
+
+    // Captures that do not pay off are not worth anything
+    value.max(0.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{planet_value, Scoring};
+    use crate::models::*;
+
+    #[test]
+    fn values_position_and_owner() {
+        let input = r#"{"planets":[
+            {"ship_count":10,"x":0,"y":0,"owner":1,"name":"home"},
+            {"ship_count":5,"x":3,"y":0,"owner":null,"name":"ours"},
+            {"ship_count":5,"x":6,"y":0,"owner":null,"name":"middle"},
+            {"ship_count":5,"x":9,"y":0,"owner":null,"name":"theirs"},
+            {"ship_count":10,"x":12,"y":0,"owner":2,"name":"enemy"}
+        ],"expeditions":[]}"#;
+        let state = State::new(serde_json::from_str(input).unwrap());
+        let value = |id: usize, owner| planet_value(&state.planets()[id], owner, 5, 6, &state);
+
+        assert!(value(1, NEUTRAL) > value(3, NEUTRAL));
+        assert!(value(2, NEUTRAL) > value(3, NEUTRAL));
+        assert!(value(3, 2) > 2. * value(3, NEUTRAL));
+
+        let squared = Scoring::Squared.model();
+        assert!(squared.score(10., 2, 5) > squared.score(10., 5, 5));
+        assert!(Scoring::Rate.model().score(10., 2, 5) < Scoring::Rate.model().score(10., 1, 9));
+    }
+}